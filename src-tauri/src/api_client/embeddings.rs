@@ -0,0 +1,43 @@
+use super::ApiError;
+use serde::{Deserialize, Serialize};
+
+/// Inputs sent per `/embeddings` call; providers reject larger batches.
+pub(super) const BATCH_SIZE: usize = 32;
+
+#[derive(Debug, Serialize)]
+pub(super) struct EmbeddingRequest<'a> {
+    pub model: &'a str,
+    pub input: &'a [String],
+    pub encoding_format: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct EmbeddingResponse {
+    pub data: Vec<Embedding>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct Embedding {
+    pub embedding: Vec<f32>,
+    #[serde(default)]
+    pub index: usize,
+}
+
+/// One vector per input, in input order, which the API does not promise to keep.
+pub(super) fn ordered(
+    response: EmbeddingResponse,
+    expected: usize,
+) -> Result<Vec<Vec<f32>>, ApiError> {
+    let mut data = response.data;
+    if data.len() != expected {
+        return Err(ApiError::Unexpected(format!(
+            "expected {expected} embeddings, got {}",
+            data.len()
+        )));
+    }
+    data.sort_by_key(|embedding| embedding.index);
+    Ok(data
+        .into_iter()
+        .map(|embedding| embedding.embedding)
+        .collect())
+}
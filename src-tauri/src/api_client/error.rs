@@ -0,0 +1,170 @@
+use super::ApiError;
+use reqwest::StatusCode;
+use serde::Serialize;
+use serde_json::Value;
+use tracing::debug;
+
+/// SiliconFlow numeric codes, which arrive in a flat `{"code", "message"}` body.
+const SILICONFLOW_INVALID_MODEL: &str = "20012";
+const SILICONFLOW_CONTEXT_LENGTH: &str = "20015";
+const SILICONFLOW_INSUFFICIENT_BALANCE: &str = "30001";
+
+/// The provider's own description of a failed request.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ProviderError {
+    /// The provider's error code, e.g. `context_length_exceeded` or `20015`.
+    pub code: Option<String>,
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "{} ({code})", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl ProviderError {
+    /// Reads the OpenAI-style `{"error": {...}}` body or SiliconFlow's flat one.
+    pub(super) fn parse(body: &str) -> Option<Self> {
+        Self::from_value(&serde_json::from_str(body).ok()?)
+    }
+
+    pub(super) fn from_value(value: &Value) -> Option<Self> {
+        let object = match value.get("error") {
+            Some(error @ Value::Object(_)) => error,
+            Some(Value::String(message)) => {
+                return Some(Self {
+                    message: message.clone(),
+                    ..Self::default()
+                })
+            }
+            _ => value,
+        };
+        let text = |key: &str| match object.get(key)? {
+            Value::String(text) => Some(text.clone()),
+            Value::Number(number) => Some(number.to_string()),
+            _ => None,
+        };
+        Some(Self {
+            code: text("code"),
+            kind: text("type"),
+            message: text("message")?,
+        })
+    }
+
+    fn mentions(&self, needles: &[&str]) -> bool {
+        let haystack = [
+            self.code.as_deref(),
+            self.kind.as_deref(),
+            Some(self.message.as_str()),
+        ]
+        .into_iter()
+        .flatten()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ");
+        needles.iter().any(|needle| haystack.contains(needle))
+    }
+}
+
+/// Maps a failed response onto an `ApiError`; the body is only logged, since
+/// it may be a large HTML page from a gateway.
+pub(super) fn from_status(status: StatusCode, body: &str) -> ApiError {
+    debug!(%status, body, "provider rejected the request");
+    if status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT {
+        return ApiError::ServerError {
+            status: status.as_u16(),
+        };
+    }
+    classify(status, body)
+}
+
+/// Maps a rejected request onto the most specific `ApiError`.
+fn classify(status: StatusCode, body: &str) -> ApiError {
+    let error = ProviderError::parse(body).unwrap_or_else(|| ProviderError {
+        message: format!("status {status}"),
+        ..ProviderError::default()
+    });
+    classify_provider_error(Some(status), error)
+}
+
+pub(super) fn classify_provider_error(
+    status: Option<StatusCode>,
+    error: ProviderError,
+) -> ApiError {
+    let code = error.code.as_deref();
+    if status == Some(StatusCode::PAYMENT_REQUIRED)
+        || code == Some(SILICONFLOW_INSUFFICIENT_BALANCE)
+        || error.mentions(&[
+            "insufficient_quota",
+            "insufficient balance",
+            "balance is insufficient",
+        ])
+    {
+        ApiError::InsufficientBalance(error)
+    } else if code == Some(SILICONFLOW_CONTEXT_LENGTH)
+        || error.mentions(&["context_length_exceeded", "context length", "max_seq_len"])
+    {
+        ApiError::ContextLengthExceeded(error)
+    } else if error.mentions(&["content_filter", "content_policy", "sensitive"]) {
+        ApiError::ContentFiltered(error)
+    } else if code == Some(SILICONFLOW_INVALID_MODEL)
+        || error.mentions(&["model_not_found", "model does not exist", "invalid model"])
+    {
+        ApiError::InvalidModel(error)
+    } else {
+        match status {
+            Some(status) if status.is_client_error() => ApiError::BadRequest(error),
+            Some(status) => ApiError::ServerError {
+                status: status.as_u16(),
+            },
+            // Reported inside an otherwise successful stream.
+            None => ApiError::Stream(error.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_errors_drop_the_body_and_are_retryable() {
+        let err = from_status(StatusCode::BAD_GATEWAY, "<html>upstream down</html>");
+        assert!(matches!(err, ApiError::ServerError { status: 502 }));
+        assert!(err.is_retryable());
+        assert!(!err.to_string().contains("html"));
+    }
+
+    #[test]
+    fn request_timeout_is_retryable() {
+        assert!(from_status(StatusCode::REQUEST_TIMEOUT, "").is_retryable());
+    }
+
+    #[test]
+    fn unparseable_client_errors_are_final() {
+        let err = from_status(StatusCode::NOT_FOUND, "not json");
+        assert!(matches!(err, ApiError::BadRequest(_)));
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn provider_codes_pick_the_specific_error() {
+        let err = from_status(
+            StatusCode::BAD_REQUEST,
+            r#"{"code": 20015, "message": "length of prompt exceeds"}"#,
+        );
+        assert!(matches!(err, ApiError::ContextLengthExceeded(_)));
+
+        let err = from_status(
+            StatusCode::BAD_REQUEST,
+            r#"{"error": {"code": "model_not_found", "message": "no such model"}}"#,
+        );
+        assert!(matches!(err, ApiError::InvalidModel(_)));
+    }
+}
@@ -0,0 +1,185 @@
+use crate::storage::NetworkSettings;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, thiserror::Error)]
+pub enum HttpConfigError {
+    #[error("invalid proxy: {0}")]
+    Proxy(reqwest::Error),
+    #[error("CA bundle path must be absolute: {0}")]
+    CaBundlePath(PathBuf),
+    #[error("cannot read CA bundle: {0}")]
+    CaBundleIo(#[from] std::io::Error),
+    #[error("invalid CA bundle: {0}")]
+    CaBundle(reqwest::Error),
+    #[error("invalid header {name}: {message}")]
+    Header {
+        index: usize,
+        name: String,
+        message: String,
+    },
+    #[error("cannot build http client: {0}")]
+    Client(reqwest::Error),
+}
+
+/// Checks the network settings without touching the disk: the CA bundle is
+/// only read, and reported if unreadable, when the client is built.
+pub fn check_network(network: &NetworkSettings) -> Result<(), HttpConfigError> {
+    default_headers(network)?;
+    if let Some(proxy) = network.proxy() {
+        reqwest::Proxy::all(proxy).map_err(HttpConfigError::Proxy)?;
+    }
+    ca_bundle_path(network)?;
+    Ok(())
+}
+
+/// Builds the client shared by every provider from the network settings.
+pub fn build_http_client(network: &NetworkSettings) -> Result<reqwest::Client, HttpConfigError> {
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(Duration::from_millis(network.connect_timeout_ms))
+        .timeout(Duration::from_millis(network.request_timeout_ms))
+        .default_headers(default_headers(network)?);
+
+    if let Some(proxy) = network.proxy() {
+        builder = builder.proxy(reqwest::Proxy::all(proxy).map_err(HttpConfigError::Proxy)?);
+    }
+    if let Some(path) = ca_bundle_path(network)? {
+        let pem = std::fs::read(path)?;
+        for certificate in
+            reqwest::Certificate::from_pem_bundle(&pem).map_err(HttpConfigError::CaBundle)?
+        {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    builder.build().map_err(HttpConfigError::Client)
+}
+
+fn ca_bundle_path(network: &NetworkSettings) -> Result<Option<&std::path::Path>, HttpConfigError> {
+    match network.ca_bundle() {
+        Some(path) if !path.is_absolute() => Err(HttpConfigError::CaBundlePath(path.to_path_buf())),
+        path => Ok(path),
+    }
+}
+
+fn default_headers(network: &NetworkSettings) -> Result<HeaderMap, HttpConfigError> {
+    let mut headers = HeaderMap::new();
+    for (index, header) in network.headers.iter().enumerate() {
+        let invalid = |message: String| HttpConfigError::Header {
+            index,
+            name: header.name.clone(),
+            message,
+        };
+        let name = HeaderName::from_bytes(header.name.trim().as_bytes())
+            .map_err(|err| invalid(err.to_string()))?;
+        let mut value =
+            HeaderValue::from_str(header.value.trim()).map_err(|err| invalid(err.to_string()))?;
+        value.set_sensitive(true);
+        headers.insert(name, value);
+    }
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::HeaderConfig;
+
+    /// A self-signed CA, only ever used to build clients in these tests.
+    const TEST_CA: &str = "-----BEGIN CERTIFICATE-----
+MIIBejCCASGgAwIBAgIUFMRyx7rLXoPtrm2qfPS/MYHmPGAwCgYIKoZIzj0EAwIw
+EjEQMA4GA1UEAwwHdGVzdC1jYTAgFw0yNjEwMTcwOTE5MThaGA8yMTI2MDkyMzA5
+MTkxOFowEjEQMA4GA1UEAwwHdGVzdC1jYTBZMBMGByqGSM49AgEGCCqGSM49AwEH
+A0IABIjRinnEGN4pWjtcSCHfp1KW6Rx7LMjttvf1aKK7R5fVxH/LwRauJg/zVAMK
+a4FUQbIa8JG8zwPDvbMcXZmuHgejUzBRMB0GA1UdDgQWBBR5Vr96XcUmBt8PIv9z
+BFdHUelEXDAfBgNVHSMEGDAWgBR5Vr96XcUmBt8PIv9zBFdHUelEXDAPBgNVHRMB
+Af8EBTADAQH/MAoGCCqGSM49BAMCA0cAMEQCIBjPQQhI9IXuCzZp13ZFFEdi2uLu
+ITSEi6JRENvbibL9AiAIAE9pFy4Trzg7cKwhcFGIUnOf3EYjVnr370FRjagMRQ==
+-----END CERTIFICATE-----
+";
+
+    fn with_proxy(proxy: &str) -> NetworkSettings {
+        NetworkSettings {
+            proxy: Some(proxy.to_string()),
+            ..NetworkSettings::default()
+        }
+    }
+
+    fn with_ca_bundle(path: PathBuf) -> NetworkSettings {
+        NetworkSettings {
+            ca_bundle: Some(path),
+            ..NetworkSettings::default()
+        }
+    }
+
+    #[test]
+    fn http_and_socks_proxies_build_a_client() {
+        for proxy in ["http://127.0.0.1:8080", "socks5://127.0.0.1:1080", "  "] {
+            assert!(check_network(&with_proxy(proxy)).is_ok(), "{proxy}");
+            assert!(build_http_client(&with_proxy(proxy)).is_ok(), "{proxy}");
+        }
+    }
+
+    #[test]
+    fn malformed_proxies_are_rejected() {
+        assert!(matches!(
+            check_network(&with_proxy("http://[::1")),
+            Err(HttpConfigError::Proxy(_))
+        ));
+    }
+
+    #[test]
+    fn ca_bundles_are_read_only_when_building() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let path = dir.path().join("ca.pem");
+        let network = with_ca_bundle(path.clone());
+        assert!(check_network(&network).is_ok());
+        assert!(matches!(
+            build_http_client(&network),
+            Err(HttpConfigError::CaBundleIo(_))
+        ));
+
+        std::fs::write(&path, TEST_CA).unwrap();
+        assert!(build_http_client(&network).is_ok());
+
+        std::fs::write(
+            &path,
+            "-----BEGIN CERTIFICATE-----\nnot base64\n-----END CERTIFICATE-----\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            build_http_client(&network),
+            Err(HttpConfigError::CaBundle(_))
+        ));
+    }
+
+    #[test]
+    fn relative_ca_bundle_paths_are_rejected() {
+        assert!(matches!(
+            check_network(&with_ca_bundle(PathBuf::from("ca.pem"))),
+            Err(HttpConfigError::CaBundlePath(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_headers_report_their_index() {
+        let network = NetworkSettings {
+            headers: vec![
+                HeaderConfig {
+                    name: "X-Team".to_string(),
+                    value: "translate".to_string(),
+                },
+                HeaderConfig {
+                    name: "bad header".to_string(),
+                    value: "x".to_string(),
+                },
+            ],
+            ..NetworkSettings::default()
+        };
+        assert!(matches!(
+            check_network(&network),
+            Err(HttpConfigError::Header { index: 1, .. })
+        ));
+    }
+}
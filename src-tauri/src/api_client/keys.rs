@@ -0,0 +1,218 @@
+use super::ApiError;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const RATE_LIMIT_COOLDOWN: Duration = Duration::from_secs(60);
+const UNAUTHORIZED_COOLDOWN: Duration = Duration::from_secs(10 * 60);
+
+/// One of the team's API keys, identified by a user-chosen name.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub name: String,
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyHealthStatus {
+    Healthy,
+    RateLimited,
+    Unauthorized,
+}
+
+/// Per-key state shown in the UI; never contains the secret.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyHealth {
+    pub name: String,
+    pub status: KeyHealthStatus,
+    pub cooldown_remaining_ms: u64,
+    pub consecutive_failures: u32,
+}
+
+#[derive(Debug)]
+struct KeyState {
+    /// Hash of the secret, so replacing a key under the same name clears its cooldown.
+    fingerprint: u64,
+    status: KeyHealthStatus,
+    cooldown_until: Instant,
+    consecutive_failures: u32,
+}
+
+/// Tracks which keys recently failed and should be skipped for a while.
+#[derive(Debug, Clone, Default)]
+pub struct KeyHealthTracker {
+    states: Arc<Mutex<HashMap<String, KeyState>>>,
+}
+
+impl KeyHealthTracker {
+    /// Keys to try in order: healthy ones first-to-last, or the one that recovers
+    /// soonest when every key is cooling down.
+    pub(super) fn rotation<'a>(&self, keys: &'a [ApiKey]) -> Vec<&'a ApiKey> {
+        let states = self.states.lock().expect("key health lock");
+        let now = Instant::now();
+        let cooldown = |key: &ApiKey| {
+            states
+                .get(&key.name)
+                .filter(|state| state.fingerprint == fingerprint(key))
+                .map(|state| state.cooldown_until)
+                .filter(|until| *until > now)
+        };
+
+        let available: Vec<&ApiKey> = keys.iter().filter(|key| cooldown(key).is_none()).collect();
+        if !available.is_empty() {
+            return available;
+        }
+        keys.iter()
+            .min_by_key(|key| cooldown(key))
+            .into_iter()
+            .collect()
+    }
+
+    pub(super) fn record_success(&self, key: &ApiKey) {
+        self.states
+            .lock()
+            .expect("key health lock")
+            .remove(&key.name);
+    }
+
+    /// Puts `key` on cooldown after a rate limit or authorization failure.
+    pub(super) fn record_failure(&self, key: &ApiKey, err: &ApiError) {
+        let (status, cooldown) = match err {
+            ApiError::RateLimited => (KeyHealthStatus::RateLimited, RATE_LIMIT_COOLDOWN),
+            ApiError::Unauthorized => (KeyHealthStatus::Unauthorized, UNAUTHORIZED_COOLDOWN),
+            _ => return,
+        };
+        let mut states = self.states.lock().expect("key health lock");
+        let fingerprint = fingerprint(key);
+        let failures = states
+            .get(&key.name)
+            .filter(|state| state.fingerprint == fingerprint)
+            .map_or(0, |state| state.consecutive_failures);
+        states.insert(
+            key.name.clone(),
+            KeyState {
+                fingerprint,
+                status,
+                cooldown_until: Instant::now() + cooldown,
+                consecutive_failures: failures + 1,
+            },
+        );
+    }
+
+    pub(super) fn report(&self, keys: &[ApiKey]) -> Vec<KeyHealth> {
+        let states = self.states.lock().expect("key health lock");
+        let now = Instant::now();
+        keys.iter()
+            .map(|key| {
+                let state = states
+                    .get(&key.name)
+                    .filter(|state| state.fingerprint == fingerprint(key));
+                let remaining = state.map_or(Duration::ZERO, |state| {
+                    state.cooldown_until.saturating_duration_since(now)
+                });
+                KeyHealth {
+                    name: key.name.clone(),
+                    status: match state {
+                        Some(state) if !remaining.is_zero() => state.status,
+                        _ => KeyHealthStatus::Healthy,
+                    },
+                    cooldown_remaining_ms: remaining.as_millis() as u64,
+                    consecutive_failures: state.map_or(0, |state| state.consecutive_failures),
+                }
+            })
+            .collect()
+    }
+}
+
+fn fingerprint(key: &ApiKey) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.secret.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str, secret: &str) -> ApiKey {
+        ApiKey {
+            name: name.to_string(),
+            secret: secret.to_string(),
+        }
+    }
+
+    fn names(keys: Vec<&ApiKey>) -> Vec<&str> {
+        keys.into_iter().map(|key| key.name.as_str()).collect()
+    }
+
+    fn set_cooldown(tracker: &KeyHealthTracker, name: &str, until: Instant) {
+        let mut states = tracker.states.lock().unwrap();
+        states.get_mut(name).unwrap().cooldown_until = until;
+    }
+
+    #[test]
+    fn failing_keys_leave_the_rotation() {
+        let tracker = KeyHealthTracker::default();
+        let keys = [key("a", "1"), key("b", "2"), key("c", "3")];
+        assert_eq!(names(tracker.rotation(&keys)), ["a", "b", "c"]);
+
+        tracker.record_failure(&keys[0], &ApiError::RateLimited);
+        tracker.record_failure(&keys[2], &ApiError::Unauthorized);
+        assert_eq!(names(tracker.rotation(&keys)), ["b"]);
+
+        // Other errors say nothing about the key.
+        tracker.record_failure(&keys[1], &ApiError::ServerError { status: 500 });
+        assert_eq!(names(tracker.rotation(&keys)), ["b"]);
+    }
+
+    #[test]
+    fn keys_return_when_their_cooldown_expires_or_they_succeed() {
+        let tracker = KeyHealthTracker::default();
+        let keys = [key("a", "1"), key("b", "2")];
+        tracker.record_failure(&keys[0], &ApiError::RateLimited);
+        tracker.record_failure(&keys[1], &ApiError::RateLimited);
+
+        set_cooldown(&tracker, "a", Instant::now() - Duration::from_millis(1));
+        assert_eq!(names(tracker.rotation(&keys)), ["a"]);
+
+        tracker.record_success(&keys[1]);
+        assert_eq!(names(tracker.rotation(&keys)), ["a", "b"]);
+        assert_eq!(tracker.report(&keys)[1].consecutive_failures, 0);
+    }
+
+    #[test]
+    fn with_every_key_cooling_down_the_soonest_to_recover_is_tried() {
+        let tracker = KeyHealthTracker::default();
+        let keys = [key("a", "1"), key("b", "2")];
+        tracker.record_failure(&keys[0], &ApiError::Unauthorized);
+        tracker.record_failure(&keys[1], &ApiError::RateLimited);
+        assert_eq!(names(tracker.rotation(&keys)), ["b"]);
+
+        set_cooldown(&tracker, "a", Instant::now() + Duration::from_secs(1));
+        assert_eq!(names(tracker.rotation(&keys)), ["a"]);
+        assert!(tracker.rotation(&[]).is_empty());
+    }
+
+    #[test]
+    fn replacing_a_secret_clears_its_cooldown() {
+        let tracker = KeyHealthTracker::default();
+        tracker.record_failure(&key("a", "old"), &ApiError::Unauthorized);
+        tracker.record_failure(&key("a", "old"), &ApiError::Unauthorized);
+
+        let report = tracker.report(&[key("a", "old")]);
+        assert_eq!(report[0].status, KeyHealthStatus::Unauthorized);
+        assert_eq!(report[0].consecutive_failures, 2);
+        assert!(report[0].cooldown_remaining_ms > 0);
+
+        let replaced = [key("a", "new")];
+        assert_eq!(names(tracker.rotation(&replaced)), ["a"]);
+        assert_eq!(
+            tracker.report(&replaced)[0].status,
+            KeyHealthStatus::Healthy
+        );
+    }
+}
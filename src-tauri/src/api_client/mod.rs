@@ -1,10 +1,37 @@
+use crate::platform_windows::SelectionSource;
+use crate::storage::{
+    BudgetUsage, BudgetWarning, GlossaryEntry, GlossaryViolation, MemoryMatch, NetworkSettings,
+    PromptProfile, PromptVariables,
+};
+use async_trait::async_trait;
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::Instant;
 use thiserror::Error;
 use tracing::{info, warn};
 
-const DEFAULT_ENDPOINT: &str = "https://api.siliconflow.cn/v1/chat/completions";
+mod embeddings;
+mod error;
+mod http;
+mod keys;
+mod models;
+mod retry;
+mod stream;
+
+pub use error::ProviderError;
+pub use http::{build_http_client, check_network, HttpConfigError};
+pub use keys::{ApiKey, KeyHealth, KeyHealthTracker};
+pub use models::{ModelCaches, ModelInfo};
+pub use retry::RetryPolicy;
+
+use embeddings::{EmbeddingRequest, EmbeddingResponse};
+use models::{ModelCache, ModelList};
+
+pub const SILICONFLOW_PROVIDER: &str = "siliconflow";
+const SILICONFLOW_ENDPOINT: &str = "https://api.siliconflow.cn/v1/chat/completions";
+pub const OPENAI_PROVIDER: &str = "openai";
+const OPENAI_ENDPOINT: &str = "https://api.openai.com/v1/chat/completions";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TranslationRequest {
@@ -12,12 +39,101 @@ pub struct TranslationRequest {
     pub source_lang: String,
     pub target_lang: String,
     pub model: String,
+    #[serde(default)]
+    pub stream: bool,
+    #[serde(default)]
+    pub request_id: Option<String>,
+    /// Set when the request was triggered by a text selection rather than by the user.
+    #[serde(default)]
+    pub selection_source: Option<SelectionSource>,
+    /// Surrounding text made available to templates as `{context}`.
+    #[serde(default)]
+    pub context: Option<String>,
+    /// Filled in by the core from the active prompt profile.
+    #[serde(skip)]
+    pub prompt: Option<PromptProfile>,
+    /// Glossary entries that occur in `text`, filled in by the core.
+    #[serde(skip)]
+    pub glossary: Vec<GlossaryEntry>,
+    /// Similar segments from the translation memory, sent as example turns.
+    #[serde(skip)]
+    pub references: Vec<MemoryMatch>,
+    /// Filled in by the core from the settings.
+    #[serde(skip)]
+    pub retry_policy: RetryPolicy,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TranslationResponse {
+    pub request_id: Option<String>,
     pub translated_text: String,
     pub detected_source_lang: Option<String>,
+    #[serde(default)]
+    pub direction: TranslationDirection,
+    #[serde(default)]
+    pub glossary_violations: Vec<GlossaryViolation>,
+    /// Tokens billed for the request, when the provider reported them.
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+    /// True when the translation came from the cache instead of the provider.
+    #[serde(default)]
+    pub cached: bool,
+    /// Name of the key that served the request.
+    #[serde(skip)]
+    pub api_key_name: Option<String>,
+}
+
+/// The provider's `usage` object.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
+/// The language pair a translation actually used.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TranslationDirection {
+    pub source_lang: String,
+    pub target_lang: String,
+    /// True when the target was replaced by the secondary target language.
+    pub swapped: bool,
+}
+
+/// Incremental piece of a streamed translation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranslationDelta {
+    pub request_id: Option<String>,
+    pub delta: String,
+}
+
+/// Announces that a failed request will be attempted again after `delay_ms`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranslationRetry {
+    pub request_id: Option<String>,
+    /// The attempt about to be made, counting from 1.
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub delay_ms: u64,
+    pub reason: String,
+}
+
+/// Receives progress while a translation is running.
+pub trait TranslationObserver: Send + Sync {
+    fn on_delta(&self, delta: &TranslationDelta);
+
+    fn on_retry(&self, _retry: &TranslationRetry) {}
+
+    fn on_budget_warning(&self, _warning: &BudgetWarning) {}
 }
 
 #[derive(Debug, Error)]
@@ -32,78 +148,509 @@ pub enum ApiError {
     RateLimited,
     #[error("model unavailable")]
     ModelUnavailable,
+    #[error("translation cancelled")]
+    Cancelled,
+    #[error("unknown provider: {0}")]
+    UnknownProvider(String),
+    /// A client-supplied request id uses the prefix of generated ids.
+    #[error("reserved request id: {0}")]
+    ReservedRequestId(String),
+    #[error("budget exceeded: {0}")]
+    BudgetExceeded(BudgetUsage),
+    #[error("insufficient balance: {0}")]
+    InsufficientBalance(ProviderError),
+    #[error("context length exceeded: {0}")]
+    ContextLengthExceeded(ProviderError),
+    #[error("content filtered: {0}")]
+    ContentFiltered(ProviderError),
+    #[error("invalid model: {0}")]
+    InvalidModel(ProviderError),
+    #[error("bad request: {0}")]
+    BadRequest(ProviderError),
+    /// The provider failed or timed out (5xx or 408).
+    #[error("server error: status {status}")]
+    ServerError { status: u16 },
+    #[error("stream error: {0}")]
+    Stream(String),
     #[error("unexpected response: {0}")]
     Unexpected(String),
 }
 
+impl ApiError {
+    /// Stable identifier the frontend maps to a localized message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::MissingApiKey => "missing_api_key",
+            Self::Http(_) => "network",
+            Self::Unauthorized => "unauthorized",
+            Self::RateLimited => "rate_limited",
+            Self::ModelUnavailable => "model_unavailable",
+            Self::InsufficientBalance(_) => "insufficient_balance",
+            Self::ContextLengthExceeded(_) => "context_length_exceeded",
+            Self::ContentFiltered(_) => "content_filtered",
+            Self::InvalidModel(_) => "invalid_model",
+            Self::BadRequest(_) => "bad_request",
+            Self::Cancelled => "cancelled",
+            Self::UnknownProvider(_) => "unknown_provider",
+            Self::ReservedRequestId(_) => "reserved_request_id",
+            Self::BudgetExceeded(_) => "budget_exceeded",
+            Self::ServerError { .. } => "server_error",
+            Self::Stream(_) => "stream",
+            Self::Unexpected(_) => "unexpected",
+        }
+    }
+
+    /// Whether the same request may succeed if sent again later.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::Http(_)
+                | Self::RateLimited
+                | Self::ModelUnavailable
+                | Self::ServerError { .. }
+                | Self::Stream(_)
+        )
+    }
+
+    /// The provider's own error details, when its response carried any.
+    pub fn provider_error(&self) -> Option<&ProviderError> {
+        match self {
+            Self::InsufficientBalance(error)
+            | Self::ContextLengthExceeded(error)
+            | Self::ContentFiltered(error)
+            | Self::InvalidModel(error)
+            | Self::BadRequest(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// A backend able to turn a `TranslationRequest` into a translation.
+#[async_trait]
+pub trait TranslationProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn translate(
+        &self,
+        request: TranslationRequest,
+        keys: &[ApiKey],
+        observer: Option<&dyn TranslationObserver>,
+    ) -> Result<TranslationResponse, ApiError>;
+
+    /// Makes the cheapest authenticated call the backend offers.
+    async fn check_key(&self, api_key: &str) -> Result<(), ApiError>;
+
+    /// Cooldown state of `keys` as seen by this provider.
+    fn key_health(&self, keys: &[ApiKey]) -> Vec<KeyHealth>;
+
+    /// Chat-capable models, served from cache unless `refresh` is set or it expired.
+    async fn list_models(&self, api_key: &str, refresh: bool) -> Result<Vec<ModelInfo>, ApiError>;
+
+    /// The cached model list, if one was fetched recently.
+    fn cached_models(&self) -> Option<Vec<ModelInfo>>;
+
+    /// One embedding vector per text, in the same order.
+    async fn embed(
+        &self,
+        texts: &[String],
+        model: &str,
+        keys: &[ApiKey],
+        policy: &RetryPolicy,
+    ) -> Result<Vec<Vec<f32>>, ApiError>;
+}
+
+/// Outcome of testing an API key against a provider.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyCheck {
+    pub status: KeyCheckStatus,
+    /// `ApiError::code` of the failure.
+    pub code: Option<&'static str>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyCheckStatus {
+    Valid,
+    Unauthorized,
+    NetworkFailure,
+}
+
+impl From<Result<(), ApiError>> for KeyCheck {
+    fn from(result: Result<(), ApiError>) -> Self {
+        let status = match &result {
+            Ok(()) => KeyCheckStatus::Valid,
+            Err(ApiError::Unauthorized | ApiError::MissingApiKey) => KeyCheckStatus::Unauthorized,
+            Err(_) => KeyCheckStatus::NetworkFailure,
+        };
+        let err = result.err();
+        Self {
+            status,
+            code: err.as_ref().map(ApiError::code),
+            message: err.map(|err| err.to_string()),
+        }
+    }
+}
+
+/// Client for any OpenAI-compatible chat completions endpoint.
 #[derive(Debug, Clone)]
 pub struct ApiClient {
+    name: String,
     endpoint: String,
     http: reqwest::Client,
+    key_health: Arc<KeyHealthTracker>,
+    models: ModelCache,
 }
 
 impl ApiClient {
     pub fn new() -> Self {
-        let http = reqwest::Client::builder()
-            .timeout(Duration::from_secs(20))
-            .build()
-            .expect("http client");
+        Self::with_endpoint(SILICONFLOW_PROVIDER, SILICONFLOW_ENDPOINT)
+    }
+
+    pub fn openai() -> Self {
+        Self::with_endpoint(OPENAI_PROVIDER, OPENAI_ENDPOINT)
+    }
+
+    pub fn with_endpoint(name: impl Into<String>, endpoint: impl Into<String>) -> Self {
+        let http = build_http_client(&NetworkSettings::default()).expect("http client");
         Self {
-            endpoint: DEFAULT_ENDPOINT.to_string(),
+            name: name.into(),
+            endpoint: endpoint.into(),
             http,
+            key_health: Arc::default(),
+            models: ModelCache::default(),
+        }
+    }
+
+    /// Shares key cooldowns with other clients, so they survive rebuilding the client.
+    pub fn with_key_health(mut self, key_health: Arc<KeyHealthTracker>) -> Self {
+        self.key_health = key_health;
+        self
+    }
+
+    /// Sends requests through `http`, which carries the proxy, headers and timeouts.
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Keeps the model list in `caches`, under this client's name and endpoint;
+    /// call it after `with_base_url`.
+    pub fn with_model_caches(mut self, caches: &ModelCaches) -> Self {
+        self.models = caches.get(&self.name, &self.endpoint);
+        self
+    }
+
+    /// Points the client at another OpenAI-compatible base URL such as `https://host/v1`.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.endpoint = format!("{}/chat/completions", base_url.trim_end_matches('/'));
+        self
+    }
+
+    /// `GET /models` next to the chat completions endpoint.
+    fn models_endpoint(&self) -> String {
+        match self.endpoint.strip_suffix("/chat/completions") {
+            Some(base) => format!("{base}/models"),
+            None => self.endpoint.clone(),
+        }
+    }
+
+    /// `POST /embeddings` next to the chat completions endpoint.
+    fn embeddings_endpoint(&self) -> String {
+        match self.endpoint.strip_suffix("/chat/completions") {
+            Some(base) => format!("{base}/embeddings"),
+            None => self.endpoint.clone(),
+        }
+    }
+
+    pub async fn check_key(&self, api_key: &str) -> Result<(), ApiError> {
+        self.get_models(api_key).await.map(|_| ())
+    }
+
+    pub async fn list_models(
+        &self,
+        api_key: &str,
+        refresh: bool,
+    ) -> Result<Vec<ModelInfo>, ApiError> {
+        if !refresh {
+            if let Some(models) = self.models.fresh() {
+                return Ok(models);
+            }
+        }
+        let list: ModelList = self.get_models(api_key).await?.json().await?;
+        let models = models::chat_models(list.data);
+        info!(provider = %self.name, count = models.len(), "fetched model list");
+        self.models.store(models.clone());
+        Ok(models)
+    }
+
+    async fn get_models(&self, api_key: &str) -> Result<reqwest::Response, ApiError> {
+        let mut request = self.http.get(self.models_endpoint()).bearer_auth(api_key);
+        if self.name == SILICONFLOW_PROVIDER {
+            // SiliconFlow can filter server-side; other providers rely on `chat_models`.
+            request = request.query(&[("type", "text"), ("sub_type", "chat")]);
+        }
+        let response = request.send().await?;
+        match response.status() {
+            status if status.is_success() => Ok(response),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(ApiError::Unauthorized),
+            StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
+            status => {
+                let text = response.text().await.unwrap_or_default();
+                Err(error::from_status(status, &text))
+            }
         }
     }
 
+    /// Tries the keys in turn, moving on when one is rate limited or rejected.
     pub async fn translate(
         &self,
         request: TranslationRequest,
-        api_key: Option<String>,
+        keys: &[ApiKey],
+        observer: Option<&dyn TranslationObserver>,
     ) -> Result<TranslationResponse, ApiError> {
-        let api_key = api_key.ok_or(ApiError::MissingApiKey)?;
+        let request_id = request.request_id.clone();
+        let policy = request.retry_policy.clone();
         let payload = ChatCompletionRequest::from(request);
-        let mut attempt = 0;
-        let max_attempts = 3;
-
-        loop {
-            attempt += 1;
-            let response = self
-                .http
-                .post(&self.endpoint)
-                .bearer_auth(&api_key)
-                .json(&payload)
-                .send()
-                .await?;
-
-            match response.status() {
-                StatusCode::OK => {
-                    let body: ChatCompletionResponse = response.json().await?;
-                    let translated = body
-                        .choices
-                        .first()
-                        .and_then(|choice| choice.message.content.clone())
-                        .ok_or_else(|| ApiError::Unexpected("empty response".into()))?;
+        let mut last_error = ApiError::MissingApiKey;
 
+        let rotation = self.key_health.rotation(keys);
+        for (index, key) in rotation.iter().enumerate() {
+            let can_rotate = index + 1 < rotation.len();
+            match self
+                .complete(
+                    &payload,
+                    &key.secret,
+                    request_id.clone(),
+                    &policy,
+                    can_rotate,
+                    observer,
+                )
+                .await
+            {
+                Err(err @ (ApiError::RateLimited | ApiError::Unauthorized)) => {
+                    warn!(key = %key.name, %err, "api key failed, rotating to the next one");
+                    self.key_health.record_failure(key, &err);
+                    last_error = err;
+                }
+                Err(err) => return Err(err),
+                Ok(response) => {
+                    self.key_health.record_success(key);
                     return Ok(TranslationResponse {
-                        translated_text: translated,
-                        detected_source_lang: None,
+                        api_key_name: Some(key.name.clone()),
+                        ..response
                     });
                 }
-                StatusCode::UNAUTHORIZED => return Err(ApiError::Unauthorized),
-                StatusCode::TOO_MANY_REQUESTS => return Err(ApiError::RateLimited),
-                StatusCode::SERVICE_UNAVAILABLE => return Err(ApiError::ModelUnavailable),
-                status if status.is_server_error() && attempt < max_attempts => {
-                    let backoff = 200_u64 * 2_u64.pow(attempt - 1);
-                    warn!(status = ?status, attempt, "server error, retrying");
-                    tokio::time::sleep(Duration::from_millis(backoff)).await;
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Embeds `texts` with one call per `embeddings::BATCH_SIZE` inputs.
+    pub async fn embed(
+        &self,
+        texts: &[String],
+        model: &str,
+        keys: &[ApiKey],
+        policy: &RetryPolicy,
+    ) -> Result<Vec<Vec<f32>>, ApiError> {
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(embeddings::BATCH_SIZE) {
+            vectors.extend(self.embed_batch(batch, model, keys, policy).await?);
+        }
+        Ok(vectors)
+    }
+
+    /// Tries the keys in turn and retries each like `translate`.
+    async fn embed_batch(
+        &self,
+        input: &[String],
+        model: &str,
+        keys: &[ApiKey],
+        policy: &RetryPolicy,
+    ) -> Result<Vec<Vec<f32>>, ApiError> {
+        let payload = EmbeddingRequest {
+            model,
+            input,
+            encoding_format: "float",
+        };
+        let endpoint = self.embeddings_endpoint();
+        let mut last_error = ApiError::MissingApiKey;
+
+        let rotation = self.key_health.rotation(keys);
+        for (index, key) in rotation.iter().enumerate() {
+            let can_rotate = index + 1 < rotation.len();
+            let request = || {
+                self.http
+                    .post(&endpoint)
+                    .bearer_auth(&key.secret)
+                    .json(&payload)
+            };
+            match self.send(request, None, policy, can_rotate, None).await {
+                Err(err @ (ApiError::RateLimited | ApiError::Unauthorized)) => {
+                    warn!(key = %key.name, %err, "api key failed for embeddings, rotating to the next one");
+                    self.key_health.record_failure(key, &err);
+                    last_error = err;
                 }
-                status => {
-                    let text = response.text().await.unwrap_or_default();
-                    return Err(ApiError::Unexpected(format!(
-                        "status {status}, body {text}"
-                    )));
+                Err(err) => return Err(err),
+                Ok(response) => {
+                    self.key_health.record_success(key);
+                    let body: EmbeddingResponse = response.json().await?;
+                    return embeddings::ordered(body, input.len());
                 }
             }
         }
+        Err(last_error)
+    }
+
+    /// Sends a chat completion and reads the translation from its response.
+    async fn complete(
+        &self,
+        payload: &ChatCompletionRequest,
+        api_key: &str,
+        request_id: Option<String>,
+        policy: &RetryPolicy,
+        can_rotate: bool,
+        observer: Option<&dyn TranslationObserver>,
+    ) -> Result<TranslationResponse, ApiError> {
+        let request = || {
+            self.http
+                .post(&self.endpoint)
+                .bearer_auth(api_key)
+                .json(payload)
+        };
+        let response = self
+            .send(request, request_id.clone(), policy, can_rotate, observer)
+            .await?;
+        if payload.stream {
+            let (translated, usage) =
+                stream::read_stream(response, request_id.clone(), observer).await?;
+            return Ok(TranslationResponse {
+                request_id,
+                translated_text: translated,
+                usage,
+                ..TranslationResponse::default()
+            });
+        }
+        let body: ChatCompletionResponse = response.json().await?;
+        let translated = body
+            .choices
+            .first()
+            .and_then(|choice| choice.message.content.clone())
+            .ok_or_else(|| ApiError::Unexpected("empty response".into()))?;
+        Ok(TranslationResponse {
+            request_id,
+            translated_text: translated,
+            usage: body.usage,
+            ..TranslationResponse::default()
+        })
+    }
+
+    /// Sends the request built by `request`, retrying transient failures as
+    /// `policy` allows, and returns the successful response.
+    ///
+    /// A 429 is returned immediately when `can_rotate` is set, so the caller can
+    /// switch keys instead of waiting.
+    async fn send(
+        &self,
+        request: impl Fn() -> reqwest::RequestBuilder + Send,
+        request_id: Option<String>,
+        policy: &RetryPolicy,
+        can_rotate: bool,
+        observer: Option<&dyn TranslationObserver>,
+    ) -> Result<reqwest::Response, ApiError> {
+        let started = Instant::now();
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+            let outcome = request().send().await;
+
+            let (err, retry_after) = match outcome {
+                Err(err) if retry::is_transient(&err) => (ApiError::Http(err), None),
+                Err(err) => return Err(err.into()),
+                Ok(response) => match response.status() {
+                    status if status.is_success() => return Ok(response),
+                    StatusCode::UNAUTHORIZED => return Err(ApiError::Unauthorized),
+                    StatusCode::TOO_MANY_REQUESTS if can_rotate => {
+                        return Err(ApiError::RateLimited)
+                    }
+                    StatusCode::TOO_MANY_REQUESTS => (
+                        ApiError::RateLimited,
+                        retry::retry_after(response.headers()),
+                    ),
+                    StatusCode::SERVICE_UNAVAILABLE => (
+                        ApiError::ModelUnavailable,
+                        retry::retry_after(response.headers()),
+                    ),
+                    status => {
+                        let text = response.text().await.unwrap_or_default();
+                        let err = error::from_status(status, &text);
+                        if !err.is_retryable() {
+                            return Err(err);
+                        }
+                        (err, None)
+                    }
+                },
+            };
+
+            let Some(delay) = policy.next_delay(attempt, started, retry_after) else {
+                return Err(err);
+            };
+            warn!(%err, attempt, delay_ms = delay.as_millis() as u64, "request failed, retrying");
+            if let Some(observer) = observer {
+                observer.on_retry(&TranslationRetry {
+                    request_id: request_id.clone(),
+                    attempt: attempt + 1,
+                    max_attempts: policy.max_attempts,
+                    delay_ms: delay.as_millis() as u64,
+                    reason: err.to_string(),
+                });
+            }
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for ApiClient {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn translate(
+        &self,
+        request: TranslationRequest,
+        keys: &[ApiKey],
+        observer: Option<&dyn TranslationObserver>,
+    ) -> Result<TranslationResponse, ApiError> {
+        ApiClient::translate(self, request, keys, observer).await
+    }
+
+    async fn check_key(&self, api_key: &str) -> Result<(), ApiError> {
+        ApiClient::check_key(self, api_key).await
+    }
+
+    fn key_health(&self, keys: &[ApiKey]) -> Vec<KeyHealth> {
+        self.key_health.report(keys)
+    }
+
+    async fn list_models(&self, api_key: &str, refresh: bool) -> Result<Vec<ModelInfo>, ApiError> {
+        ApiClient::list_models(self, api_key, refresh).await
+    }
+
+    fn cached_models(&self) -> Option<Vec<ModelInfo>> {
+        self.models.fresh()
+    }
+
+    async fn embed(
+        &self,
+        texts: &[String],
+        model: &str,
+        keys: &[ApiKey],
+        policy: &RetryPolicy,
+    ) -> Result<Vec<Vec<f32>>, ApiError> {
+        ApiClient::embed(self, texts, model, keys, policy).await
     }
 }
 
@@ -114,6 +661,14 @@ struct ChatCompletionRequest {
     temperature: f32,
     top_p: f32,
     stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+}
+
+/// Asks for a final chunk carrying `usage`, which streams omit by default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StreamOptions {
+    include_usage: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -125,6 +680,8 @@ struct ChatMessage {
 #[derive(Debug, Clone, Deserialize)]
 struct ChatCompletionResponse {
     choices: Vec<ChatChoice>,
+    #[serde(default)]
+    usage: Option<TokenUsage>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -139,29 +696,58 @@ struct ChatMessageContent {
 
 impl From<TranslationRequest> for ChatCompletionRequest {
     fn from(request: TranslationRequest) -> Self {
-        let system_prompt = "你是专业翻译。忠实准确，保持术语一致，不扩写不发挥。只输出译文。";
-        let user_prompt = format!(
-            "将以下文本从 {} 翻译为 {}:\n{}",
-            request.source_lang, request.target_lang, request.text
-        );
+        let profile = request.prompt.unwrap_or_default();
+        let variables = PromptVariables {
+            source_lang: &request.source_lang,
+            target_lang: &request.target_lang,
+            text: &request.text,
+            context: request.context.as_deref().unwrap_or_default(),
+        };
+        let (mut system_prompt, user_prompt) = profile.render(&variables);
+        if !request.glossary.is_empty() {
+            system_prompt.push_str("\n术语表（以下术语必须使用指定译法）：");
+            for entry in &request.glossary {
+                system_prompt.push_str(&format!("\n- {} → {}", entry.term, entry.translation));
+            }
+        }
+        if !request.references.is_empty() {
+            system_prompt.push_str("\n此前对相似原文的译文已作为示例给出，请保持一致的措辞。");
+        }
+
+        let mut messages = vec![ChatMessage {
+            role: "system".to_string(),
+            content: system_prompt,
+        }];
+        for reference in &request.references {
+            let (_, example) = profile.render(&PromptVariables {
+                text: &reference.source_text,
+                ..variables
+            });
+            messages.push(ChatMessage {
+                role: "user".to_string(),
+                content: example,
+            });
+            messages.push(ChatMessage {
+                role: "assistant".to_string(),
+                content: reference.target_text.clone(),
+            });
+        }
+        messages.push(ChatMessage {
+            role: "user".to_string(),
+            content: user_prompt,
+        });
 
         info!(model = %request.model, "translation request");
 
         Self {
             model: request.model,
-            messages: vec![
-                ChatMessage {
-                    role: "system".to_string(),
-                    content: system_prompt.to_string(),
-                },
-                ChatMessage {
-                    role: "user".to_string(),
-                    content: user_prompt,
-                },
-            ],
+            messages,
             temperature: 0.2,
             top_p: 0.95,
-            stream: false,
+            stream: request.stream,
+            stream_options: request.stream.then_some(StreamOptions {
+                include_usage: true,
+            }),
         }
     }
 }
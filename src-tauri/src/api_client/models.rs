@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const MODELS_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Substrings of model ids that cannot serve chat completions.
+const NON_CHAT_MARKERS: &[&str] = &[
+    "embed",
+    "rerank",
+    "whisper",
+    "tts",
+    "speech",
+    "audio",
+    "dall-e",
+    "image",
+    "flux",
+    "stable-diffusion",
+    "moderation",
+    "video",
+];
+
+/// A model offered by the provider's `/models` endpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    #[serde(default)]
+    pub owned_by: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct ModelList {
+    pub data: Vec<ModelInfo>,
+}
+
+type CachedModels = Option<(Instant, Vec<ModelInfo>)>;
+
+/// Last fetched model list, reused until it is older than `MODELS_TTL`.
+#[derive(Debug, Clone, Default)]
+pub(super) struct ModelCache {
+    entry: Arc<Mutex<CachedModels>>,
+}
+
+impl ModelCache {
+    pub(super) fn fresh(&self) -> Option<Vec<ModelInfo>> {
+        self.entry
+            .lock()
+            .expect("model cache lock")
+            .as_ref()
+            .filter(|(fetched, _)| fetched.elapsed() < MODELS_TTL)
+            .map(|(_, models)| models.clone())
+    }
+
+    pub(super) fn store(&self, models: Vec<ModelInfo>) {
+        *self.entry.lock().expect("model cache lock") = Some((Instant::now(), models));
+    }
+}
+
+/// Model lists that outlive the clients, one per provider and endpoint, so a
+/// rebuilt client still knows what its provider offers.
+#[derive(Debug, Clone, Default)]
+pub struct ModelCaches {
+    caches: Arc<Mutex<HashMap<(String, String), ModelCache>>>,
+}
+
+impl ModelCaches {
+    pub(super) fn get(&self, provider: &str, endpoint: &str) -> ModelCache {
+        self.caches
+            .lock()
+            .expect("model caches lock")
+            .entry((provider.to_string(), endpoint.to_string()))
+            .or_default()
+            .clone()
+    }
+}
+
+/// Keeps the models that look usable for chat completions, sorted by id.
+pub(super) fn chat_models(models: Vec<ModelInfo>) -> Vec<ModelInfo> {
+    let mut models: Vec<ModelInfo> = models
+        .into_iter()
+        .filter(|model| {
+            let id = model.id.to_lowercase();
+            !NON_CHAT_MARKERS.iter().any(|marker| id.contains(marker))
+        })
+        .collect();
+    models.sort_by(|left, right| left.id.cmp(&right.id));
+    models
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(id: &str) -> ModelInfo {
+        ModelInfo {
+            id: id.to_string(),
+            owned_by: None,
+        }
+    }
+
+    #[test]
+    fn chat_models_drops_other_kinds_and_sorts_by_id() {
+        let models = chat_models(
+            [
+                "Qwen/Qwen2.5-7B-Instruct",
+                "text-embedding-3-small",
+                "whisper-1",
+                "gpt-4o-mini",
+                "black-forest-labs/FLUX.1-schnell",
+                "BAAI/bge-reranker-v2-m3",
+                "deepseek-ai/DeepSeek-V3",
+                "tts-1-hd",
+                "dall-e-3",
+                "omni-moderation-latest",
+            ]
+            .map(model)
+            .to_vec(),
+        );
+        let ids: Vec<&str> = models.iter().map(|model| model.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "Qwen/Qwen2.5-7B-Instruct",
+                "deepseek-ai/DeepSeek-V3",
+                "gpt-4o-mini",
+            ]
+        );
+    }
+
+    #[test]
+    fn caches_are_shared_per_provider_and_endpoint() {
+        let caches = ModelCaches::default();
+        caches
+            .get("openai", "https://a.example/v1")
+            .store(vec![model("gpt-4o")]);
+        assert_eq!(
+            caches.get("openai", "https://a.example/v1").fresh(),
+            Some(vec![model("gpt-4o")])
+        );
+        assert_eq!(caches.get("openai", "https://b.example/v1").fresh(), None);
+        assert_eq!(caches.get("other", "https://a.example/v1").fresh(), None);
+    }
+}
@@ -0,0 +1,177 @@
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use serde::{Deserialize, Serialize};
+use std::error::Error as _;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Longest exponential delay between two attempts, before jitter.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How failed requests to a provider are retried.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one.
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    /// Fraction of each delay that is randomized, from 0 (none) to 1.
+    pub jitter: f64,
+    /// Retries stop once this much time has passed since the first attempt.
+    pub deadline_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay_ms: 200,
+            jitter: 0.2,
+            deadline_ms: 30_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the attempt after `attempt`, or `None` when the policy is exhausted.
+    ///
+    /// A server-provided `Retry-After` replaces the exponential delay.
+    pub fn next_delay(
+        &self,
+        attempt: u32,
+        started: Instant,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        let delay = retry_after.unwrap_or_else(|| self.backoff(attempt));
+        let deadline = Duration::from_millis(self.deadline_ms);
+        (started.elapsed() + delay <= deadline).then_some(delay)
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = Duration::from_millis(self.base_delay_ms)
+            .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(MAX_BACKOFF);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return exponential;
+        }
+        let factor = rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter);
+        exponential.mul_f64(factor)
+    }
+}
+
+/// Reads `Retry-After` as either delay seconds or an HTTP date.
+pub(super) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Timeouts, refused connections and resets that are worth another attempt.
+pub(super) fn is_transient(err: &reqwest::Error) -> bool {
+    if err.is_timeout() || err.is_connect() {
+        return true;
+    }
+    let mut source = err.source();
+    while let Some(cause) = source {
+        if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+            use std::io::ErrorKind::*;
+            return matches!(
+                io.kind(),
+                ConnectionReset | ConnectionAborted | BrokenPipe | TimedOut | UnexpectedEof
+            );
+        }
+        source = cause.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn policy(max_attempts: u32, jitter: f64) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay_ms: 100,
+            jitter,
+            deadline_ms: 60_000,
+        }
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_http_dates() {
+        assert_eq!(retry_after(&headers(" 7 ")), Some(Duration::from_secs(7)));
+
+        let later = SystemTime::now() + Duration::from_secs(120);
+        let delay = retry_after(&headers(&httpdate::fmt_http_date(later))).unwrap();
+        assert!(delay > Duration::from_secs(115) && delay <= Duration::from_secs(120));
+
+        let past = SystemTime::now() - Duration::from_secs(120);
+        assert_eq!(
+            retry_after(&headers(&httpdate::fmt_http_date(past))),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after(&headers("soon")), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = policy(20, 0.0);
+        let started = Instant::now();
+        let delays: Vec<_> = (1..=4)
+            .map(|attempt| policy.next_delay(attempt, started, None).unwrap())
+            .collect();
+        assert_eq!(delays, [100, 200, 400, 800].map(Duration::from_millis));
+        assert_eq!(policy.next_delay(19, started, None), Some(MAX_BACKOFF));
+    }
+
+    #[test]
+    fn jitter_stays_within_its_fraction() {
+        let policy = policy(10, 0.5);
+        for _ in 0..200 {
+            let delay = policy.next_delay(3, Instant::now(), None).unwrap();
+            assert!(delay >= Duration::from_millis(200) && delay <= Duration::from_millis(600));
+        }
+    }
+
+    #[test]
+    fn attempts_and_deadline_end_the_retries() {
+        let policy = policy(3, 0.0);
+        let started = Instant::now();
+        assert!(policy.next_delay(2, started, None).is_some());
+        assert_eq!(policy.next_delay(3, started, None), None);
+
+        let server_wait = Some(Duration::from_secs(5));
+        assert_eq!(policy.next_delay(1, started, server_wait), server_wait);
+        assert_eq!(
+            policy.next_delay(1, started, Some(Duration::from_secs(61))),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn refused_connections_are_transient_and_bad_requests_are_not() {
+        let client = reqwest::Client::new();
+        let refused = client.get("http://127.0.0.1:1/").send().await.unwrap_err();
+        assert!(is_transient(&refused));
+        let invalid = client.get("http://").send().await.unwrap_err();
+        assert!(!is_transient(&invalid));
+    }
+}
@@ -0,0 +1,289 @@
+use super::error::{self, ProviderError};
+use super::{ApiError, TokenUsage, TranslationDelta, TranslationObserver};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::warn;
+
+const DONE_MARKER: &str = "[DONE]";
+
+#[derive(Debug, Clone, Deserialize)]
+struct ChatCompletionChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    error: Option<Value>,
+    /// Sent with the last chunk when `stream_options.include_usage` is set.
+    #[serde(default)]
+    usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ChunkChoice {
+    #[serde(default)]
+    delta: ChunkDelta,
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct ChunkDelta {
+    content: Option<String>,
+}
+
+/// Splits a `text/event-stream` body into the `data` payloads of each event.
+#[derive(Debug, Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    fn feed(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(bytes);
+        let mut events = Vec::new();
+
+        while let Some(pos) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if let Some(event) = self.process_line(line) {
+                events.push(event);
+            }
+        }
+
+        events
+    }
+
+    fn finish(&mut self) -> Option<String> {
+        if !self.buffer.is_empty() {
+            let line = String::from_utf8_lossy(&self.buffer).into_owned();
+            self.buffer.clear();
+            self.process_line(line.trim_end_matches('\r'));
+        }
+        self.dispatch()
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<String> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if let Some(value) = line.strip_prefix("data:") {
+            self.data
+                .push(value.strip_prefix(' ').unwrap_or(value).to_string());
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<String> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let data = self.data.join("\n");
+        self.data.clear();
+        Some(data)
+    }
+}
+
+#[derive(Debug, Default)]
+struct StreamState {
+    request_id: Option<String>,
+    text: String,
+    finish_reason: Option<String>,
+    usage: Option<TokenUsage>,
+    done: bool,
+}
+
+impl StreamState {
+    fn apply(
+        &mut self,
+        data: &str,
+        observer: Option<&dyn TranslationObserver>,
+    ) -> Result<(), ApiError> {
+        if data.trim() == DONE_MARKER {
+            self.done = true;
+            return Ok(());
+        }
+
+        let chunk: ChatCompletionChunk = serde_json::from_str(data)
+            .map_err(|err| ApiError::Stream(format!("malformed chunk: {err}")))?;
+
+        if let Some(error) = chunk.error {
+            return Err(match ProviderError::from_value(&error) {
+                Some(error) => error::classify_provider_error(None, error),
+                None => ApiError::Stream("provider error".into()),
+            });
+        }
+
+        if chunk.usage.is_some() {
+            self.usage = chunk.usage;
+        }
+
+        for choice in chunk.choices {
+            if let Some(content) = choice.delta.content.filter(|content| !content.is_empty()) {
+                self.text.push_str(&content);
+                if let Some(observer) = observer {
+                    observer.on_delta(&TranslationDelta {
+                        request_id: self.request_id.clone(),
+                        delta: content,
+                    });
+                }
+            }
+            if choice.finish_reason.is_some() {
+                self.finish_reason = choice.finish_reason;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn into_output(self) -> Result<(String, Option<TokenUsage>), ApiError> {
+        if !self.done {
+            if self.finish_reason.is_none() {
+                return Err(ApiError::Stream("stream ended before completion".into()));
+            }
+            warn!(finish_reason = ?self.finish_reason, "stream closed without [DONE]");
+        }
+        if self.text.is_empty() {
+            return Err(ApiError::Unexpected("empty response".into()));
+        }
+        Ok((self.text, self.usage))
+    }
+}
+
+pub(super) async fn read_stream(
+    mut response: reqwest::Response,
+    request_id: Option<String>,
+    observer: Option<&dyn TranslationObserver>,
+) -> Result<(String, Option<TokenUsage>), ApiError> {
+    let mut parser = SseParser::default();
+    let mut state = StreamState {
+        request_id,
+        ..StreamState::default()
+    };
+
+    while let Some(bytes) = response.chunk().await? {
+        for data in parser.feed(&bytes) {
+            state.apply(&data, observer)?;
+            if state.done {
+                return state.into_output();
+            }
+        }
+    }
+
+    if let Some(data) = parser.finish() {
+        state.apply(&data, observer)?;
+    }
+
+    state.into_output()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Deltas(Mutex<Vec<String>>);
+
+    impl TranslationObserver for Deltas {
+        fn on_delta(&self, delta: &TranslationDelta) {
+            self.0.lock().unwrap().push(delta.delta.clone());
+        }
+    }
+
+    fn chunk(content: &str) -> String {
+        serde_json::json!({ "choices": [{ "delta": { "content": content } }] }).to_string()
+    }
+
+    #[test]
+    fn parser_waits_for_the_blank_line() {
+        let mut parser = SseParser::default();
+        assert!(parser.feed(b"data: {\"a\"").is_empty());
+        assert!(parser.feed(b":1}\n").is_empty());
+        assert_eq!(parser.feed(b"\n"), vec!["{\"a\":1}"]);
+    }
+
+    #[test]
+    fn parser_handles_crlf_and_several_events_per_read() {
+        let mut parser = SseParser::default();
+        let events = parser.feed(b"data: one\r\n\r\ndata: two\r\n\r\ndata: thr");
+        assert_eq!(events, vec!["one", "two"]);
+        assert_eq!(parser.feed(b"ee\r\n\r\n"), vec!["three"]);
+    }
+
+    #[test]
+    fn parser_joins_multiline_data_and_skips_other_fields() {
+        let mut parser = SseParser::default();
+        let events =
+            parser.feed(b": keep-alive\nevent: message\nid: 7\ndata:first\ndata: second\n\n");
+        assert_eq!(events, vec!["first\nsecond"]);
+    }
+
+    #[test]
+    fn parser_keeps_utf8_split_across_reads() {
+        let mut parser = SseParser::default();
+        let bytes = "data: 你好\n\n".as_bytes();
+        assert!(parser.feed(&bytes[..8]).is_empty());
+        assert_eq!(parser.feed(&bytes[8..]), vec!["你好"]);
+    }
+
+    #[test]
+    fn finish_flushes_an_unterminated_event() {
+        let mut parser = SseParser::default();
+        assert!(parser.feed(b"data: [DONE]").is_empty());
+        assert_eq!(parser.finish().as_deref(), Some("[DONE]"));
+        assert_eq!(parser.finish(), None);
+    }
+
+    #[test]
+    fn state_collects_deltas_and_usage() {
+        let observer = Deltas::default();
+        let mut state = StreamState::default();
+        state.apply(&chunk("Hello"), Some(&observer)).unwrap();
+        state.apply(&chunk(""), Some(&observer)).unwrap();
+        state.apply(&chunk(", world"), Some(&observer)).unwrap();
+        state
+            .apply(
+                r#"{"choices":[],"usage":{"prompt_tokens":3,"completion_tokens":2,"total_tokens":5}}"#,
+                Some(&observer),
+            )
+            .unwrap();
+        state.apply(" [DONE] ", Some(&observer)).unwrap();
+
+        assert_eq!(*observer.0.lock().unwrap(), vec!["Hello", ", world"]);
+        let (text, usage) = state.into_output().unwrap();
+        assert_eq!(text, "Hello, world");
+        assert_eq!(usage.map(|usage| usage.total_tokens), Some(5));
+    }
+
+    #[test]
+    fn state_accepts_finish_reason_without_done() {
+        let mut state = StreamState::default();
+        state.apply(&chunk("Hi"), None).unwrap();
+        state
+            .apply(r#"{"choices":[{"delta":{},"finish_reason":"stop"}]}"#, None)
+            .unwrap();
+        assert_eq!(state.into_output().unwrap().0, "Hi");
+    }
+
+    #[test]
+    fn state_rejects_a_truncated_stream() {
+        let mut state = StreamState::default();
+        state.apply(&chunk("Hi"), None).unwrap();
+        assert!(matches!(state.into_output(), Err(ApiError::Stream(_))));
+    }
+
+    #[test]
+    fn state_classifies_errors_inside_the_stream() {
+        let mut state = StreamState::default();
+        let err = state
+            .apply(
+                r#"{"error":{"code":"context_length_exceeded","message":"too long"}}"#,
+                None,
+            )
+            .unwrap_err();
+        assert!(matches!(err, ApiError::ContextLengthExceeded(_)));
+        assert!(matches!(
+            state.apply("{not json", None),
+            Err(ApiError::Stream(_))
+        ));
+    }
+}
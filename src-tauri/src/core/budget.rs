@@ -0,0 +1,317 @@
+use crate::api_client::{ApiError, TokenUsage, TranslationObserver};
+use crate::storage::{
+    estimate_cost, AppSettings, BudgetAction, BudgetMetric, BudgetSettings, BudgetUsage,
+    BudgetWarning, UsageStore,
+};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Enforces `BudgetSettings` before each request is sent.
+#[derive(Clone, Default)]
+pub(super) struct BudgetGuard {
+    /// Thresholds already announced, as period key, metric and threshold bits.
+    /// Entries of past periods are dropped at the next check.
+    warned: Arc<Mutex<HashSet<(String, BudgetMetric, u64)>>>,
+    /// Estimates of requests that passed the check but are not recorded yet.
+    reserved: Arc<Mutex<Reserved>>,
+}
+
+#[derive(Debug, Default)]
+struct Reserved {
+    tokens: u64,
+    cost: f64,
+}
+
+/// Tokens and cost held against the limits while a request is in flight,
+/// released when dropped.
+pub(super) struct BudgetReservation {
+    reserved: Arc<Mutex<Reserved>>,
+    tokens: u64,
+    cost: f64,
+}
+
+impl Drop for BudgetReservation {
+    fn drop(&mut self) {
+        let mut reserved = self.reserved.lock().expect("budget lock");
+        reserved.tokens = reserved.tokens.saturating_sub(self.tokens);
+        reserved.cost = (reserved.cost - self.cost).max(0.0);
+    }
+}
+
+impl BudgetGuard {
+    /// Returns the model to use for the next request, or `BudgetExceeded`.
+    ///
+    /// The request's `estimated` usage counts towards the limits until the
+    /// returned reservation is dropped, so concurrent requests cannot all pass
+    /// on the same remaining budget. Threshold warnings are reported through
+    /// `observer` once per period.
+    pub(super) fn check(
+        &self,
+        settings: &AppSettings,
+        usage: &UsageStore,
+        model: &str,
+        estimated: &TokenUsage,
+        request_id: Option<&str>,
+        observer: &dyn TranslationObserver,
+    ) -> Result<(String, BudgetReservation), ApiError> {
+        let budget = &settings.budget;
+        // Held until the reservation is made, so two checks never see the same total.
+        let mut reserved = self.reserved.lock().expect("budget lock");
+        let mut warned = self.warned.lock().expect("budget lock");
+        let mut current = HashSet::new();
+        let mut exceeded = None;
+        for (period, metric, limit) in budget.limits() {
+            let (key, totals) = usage.current(period);
+            let status = BudgetUsage {
+                period,
+                metric,
+                used: match metric {
+                    BudgetMetric::Tokens => {
+                        (totals.prompt_tokens + totals.completion_tokens + reserved.tokens) as f64
+                    }
+                    BudgetMetric::Cost => totals.estimated_cost + reserved.cost,
+                },
+                limit,
+            };
+            current.insert(key.clone());
+            if status.fraction() >= 1.0 {
+                exceeded.get_or_insert(status);
+                continue;
+            }
+            let crossed = budget
+                .warn_at
+                .iter()
+                .copied()
+                .filter(|threshold| status.fraction() >= *threshold)
+                .reduce(f64::max);
+            if let Some(threshold) = crossed {
+                if warned.insert((key, metric, threshold.to_bits())) {
+                    warn!(%status, threshold, "budget threshold reached");
+                    observer.on_budget_warning(&BudgetWarning {
+                        request_id: request_id.map(str::to_string),
+                        usage: status,
+                        threshold,
+                        fallback_model: None,
+                    });
+                }
+            }
+        }
+        warned.retain(|(key, ..)| current.contains(key));
+
+        let model = match exceeded {
+            None => model.to_string(),
+            Some(status) => self.over_budget(budget, status, request_id, observer)?,
+        };
+        let tokens = estimated.prompt_tokens + estimated.completion_tokens;
+        let cost = estimate_cost(&settings.model_prices, &model, estimated);
+        reserved.tokens += tokens;
+        reserved.cost += cost;
+        Ok((
+            model,
+            BudgetReservation {
+                reserved: self.reserved.clone(),
+                tokens,
+                cost,
+            },
+        ))
+    }
+
+    /// The fallback model when downgrading is configured, or `BudgetExceeded`.
+    fn over_budget(
+        &self,
+        budget: &BudgetSettings,
+        status: BudgetUsage,
+        request_id: Option<&str>,
+        observer: &dyn TranslationObserver,
+    ) -> Result<String, ApiError> {
+        match (budget.on_exceeded, budget.fallback_model()) {
+            (BudgetAction::Downgrade, Some(fallback)) => {
+                warn!(%status, fallback, "budget exceeded, downgrading model");
+                observer.on_budget_warning(&BudgetWarning {
+                    request_id: request_id.map(str::to_string),
+                    usage: status,
+                    threshold: 1.0,
+                    fallback_model: Some(fallback.to_string()),
+                });
+                Ok(fallback.to_string())
+            }
+            _ => Err(ApiError::BudgetExceeded(status)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::TranslationDelta;
+    use crate::storage::ModelPrice;
+
+    #[derive(Default)]
+    struct Warnings(Mutex<Vec<BudgetWarning>>);
+
+    impl TranslationObserver for Warnings {
+        fn on_delta(&self, _delta: &TranslationDelta) {}
+
+        fn on_budget_warning(&self, warning: &BudgetWarning) {
+            self.0.lock().unwrap().push(warning.clone());
+        }
+    }
+
+    impl Warnings {
+        fn take(&self) -> Vec<BudgetWarning> {
+            std::mem::take(&mut *self.0.lock().unwrap())
+        }
+    }
+
+    fn tokens(count: u64) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: count,
+            completion_tokens: 0,
+            total_tokens: count,
+        }
+    }
+
+    fn usage() -> (tempfile::TempDir, UsageStore) {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let usage = UsageStore::open(dir.path().join("usage.json"));
+        (dir, usage)
+    }
+
+    fn token_limit(limit: u64) -> AppSettings {
+        AppSettings {
+            budget: BudgetSettings {
+                daily_tokens: Some(limit),
+                warn_at: vec![0.5, 0.8],
+                ..BudgetSettings::default()
+            },
+            ..AppSettings::default()
+        }
+    }
+
+    fn check(
+        guard: &BudgetGuard,
+        settings: &AppSettings,
+        usage: &UsageStore,
+        estimate: u64,
+        observer: &Warnings,
+    ) -> Result<(String, BudgetReservation), ApiError> {
+        guard.check(settings, usage, "model", &tokens(estimate), None, observer)
+    }
+
+    #[test]
+    fn reservations_count_until_they_are_dropped() {
+        let (_dir, usage) = usage();
+        let settings = token_limit(100);
+        let guard = BudgetGuard::default();
+        let observer = Warnings::default();
+
+        let first = check(&guard, &settings, &usage, 60, &observer).expect("first");
+        // Sees 60 of 100 in flight, so it may overshoot by its own estimate.
+        let second = check(&guard, &settings, &usage, 60, &observer).expect("second");
+        assert!(matches!(
+            check(&guard, &settings, &usage, 1, &observer),
+            Err(ApiError::BudgetExceeded(status)) if status.used == 120.0
+        ));
+
+        drop(first);
+        drop(second);
+        assert!(check(&guard, &settings, &usage, 1, &observer).is_ok());
+        assert_eq!(guard.reserved.lock().unwrap().tokens, 0);
+    }
+
+    #[test]
+    fn recorded_usage_and_reservations_add_up() {
+        let (_dir, usage) = usage();
+        usage.record("model", "key", &tokens(70), &[]);
+        let settings = token_limit(100);
+        let guard = BudgetGuard::default();
+        let observer = Warnings::default();
+
+        let _held = check(&guard, &settings, &usage, 30, &observer).expect("under");
+        assert!(check(&guard, &settings, &usage, 1, &observer).is_err());
+    }
+
+    #[test]
+    fn in_flight_cost_counts_against_cost_limits() {
+        let (_dir, usage) = usage();
+        let settings = AppSettings {
+            model_prices: vec![ModelPrice {
+                model: "model".to_string(),
+                input_per_million: 1_000_000.0,
+                output_per_million: 0.0,
+            }],
+            budget: BudgetSettings {
+                daily_cost: Some(10.0),
+                ..BudgetSettings::default()
+            },
+            ..AppSettings::default()
+        };
+        let guard = BudgetGuard::default();
+        let observer = Warnings::default();
+
+        let held = check(&guard, &settings, &usage, 10, &observer).expect("first");
+        assert!(matches!(
+            check(&guard, &settings, &usage, 1, &observer),
+            Err(ApiError::BudgetExceeded(status)) if status.metric == BudgetMetric::Cost
+        ));
+        drop(held);
+        assert!(check(&guard, &settings, &usage, 1, &observer).is_ok());
+    }
+
+    #[test]
+    fn each_threshold_is_announced_once_per_period() {
+        let (_dir, usage) = usage();
+        let settings = token_limit(100);
+        let guard = BudgetGuard::default();
+        let observer = Warnings::default();
+
+        usage.record("model", "key", &tokens(40), &[]);
+        drop(check(&guard, &settings, &usage, 0, &observer));
+        assert!(observer.take().is_empty());
+
+        usage.record("model", "key", &tokens(45), &[]);
+        drop(check(&guard, &settings, &usage, 0, &observer));
+        let warnings = observer.take();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].threshold, 0.8);
+
+        drop(check(&guard, &settings, &usage, 0, &observer));
+        assert!(observer.take().is_empty());
+    }
+
+    #[test]
+    fn warnings_of_past_periods_are_forgotten() {
+        let (_dir, usage) = usage();
+        let guard = BudgetGuard::default();
+        guard.warned.lock().unwrap().insert((
+            "2000-01-01".to_string(),
+            BudgetMetric::Tokens,
+            0.8f64.to_bits(),
+        ));
+        drop(check(
+            &guard,
+            &token_limit(100),
+            &usage,
+            0,
+            &Warnings::default(),
+        ));
+        assert!(guard.warned.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn exceeding_with_a_fallback_downgrades_the_model() {
+        let (_dir, usage) = usage();
+        usage.record("model", "key", &tokens(100), &[]);
+        let mut settings = token_limit(100);
+        settings.budget.on_exceeded = BudgetAction::Downgrade;
+        settings.budget.fallback_model = Some("cheap".to_string());
+        let observer = Warnings::default();
+
+        let (model, _held) =
+            check(&BudgetGuard::default(), &settings, &usage, 1, &observer).expect("downgrade");
+        assert_eq!(model, "cheap");
+        let warnings = observer.take();
+        assert_eq!(warnings[0].fallback_model.as_deref(), Some("cheap"));
+    }
+}
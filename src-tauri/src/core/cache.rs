@@ -0,0 +1,312 @@
+use crate::api_client::TranslationRequest;
+use crate::storage::{CacheError, CacheSettings, CacheStore, CachedTranslation};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Changed whenever the key layout changes, so old entries stop matching.
+const KEY_VERSION: &str = "v1";
+/// The disk store is trimmed to its size limit after this many writes.
+const PRUNE_EVERY: u64 = 32;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub enabled: bool,
+    pub memory_entries: usize,
+    pub disk_entries: usize,
+    pub disk_bytes: u64,
+    pub max_disk_bytes: u64,
+    /// Since the app started.
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Default)]
+struct MemoryCache {
+    entries: HashMap<String, (u64, CachedTranslation)>,
+    clock: u64,
+}
+
+impl MemoryCache {
+    fn get(&mut self, key: &str) -> Option<CachedTranslation> {
+        self.clock += 1;
+        let (used, entry) = self.entries.get_mut(key)?;
+        *used = self.clock;
+        Some(entry.clone())
+    }
+
+    fn put(&mut self, key: &str, entry: CachedTranslation, capacity: usize) {
+        self.clock += 1;
+        self.entries.insert(key.to_string(), (self.clock, entry));
+        self.shrink(capacity);
+    }
+
+    fn shrink(&mut self, capacity: usize) {
+        while self.entries.len() > capacity {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+/// Finished translations, kept in an in-memory LRU in front of a disk store.
+#[derive(Clone)]
+pub struct TranslationCache {
+    memory: Arc<Mutex<MemoryCache>>,
+    disk: CacheStore,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+    writes: Arc<AtomicU64>,
+}
+
+impl TranslationCache {
+    pub fn new(disk: CacheStore) -> Self {
+        Self {
+            memory: Arc::default(),
+            disk,
+            hits: Arc::default(),
+            misses: Arc::default(),
+            writes: Arc::default(),
+        }
+    }
+
+    /// Hashes everything that shapes the output: the normalized text, the
+    /// language pair, the model, the prompt templates, the context and the
+    /// glossary terms in play.
+    pub fn key(request: &TranslationRequest) -> String {
+        let prompt = request.prompt.clone().unwrap_or_default();
+        let mut hasher = Sha256::new();
+        let mut field = |value: &str| {
+            hasher.update((value.len() as u64).to_le_bytes());
+            hasher.update(value.as_bytes());
+        };
+        field(KEY_VERSION);
+        field(&normalize(&request.text));
+        field(&request.source_lang);
+        field(&request.target_lang);
+        field(&request.model);
+        field(&prompt.system_template);
+        field(&prompt.user_template);
+        field(request.context.as_deref().unwrap_or_default());
+        for entry in &request.glossary {
+            field(&entry.term);
+            field(&entry.translation);
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    pub fn get(&self, key: &str, settings: &CacheSettings) -> Option<CachedTranslation> {
+        let cached = self.memory.lock().expect("cache lock").get(key);
+        // The lock is released before reading from disk so other lookups are not held up.
+        let entry = cached.or_else(|| {
+            let entry = (settings.max_disk_bytes > 0)
+                .then(|| self.disk.get(key))
+                .flatten()?;
+            self.memory.lock().expect("cache lock").put(
+                key,
+                entry.clone(),
+                settings.memory_entries,
+            );
+            Some(entry)
+        });
+        let counter = if entry.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        entry
+    }
+
+    pub fn put(&self, key: &str, entry: CachedTranslation, settings: &CacheSettings) {
+        if settings.max_disk_bytes > 0 {
+            if let Err(err) = self.disk.put(key, &entry) {
+                warn!(%err, "failed to write cache entry");
+            }
+            if self.writes.fetch_add(1, Ordering::Relaxed) % PRUNE_EVERY == PRUNE_EVERY - 1 {
+                if let Err(err) = self.disk.prune(settings.max_disk_bytes) {
+                    warn!(%err, "failed to prune translation cache");
+                }
+            }
+        }
+        self.memory
+            .lock()
+            .expect("cache lock")
+            .put(key, entry, settings.memory_entries);
+    }
+
+    pub fn stats(&self, settings: &CacheSettings) -> Result<CacheStats, CacheError> {
+        let (disk_entries, disk_bytes) = self.disk.size()?;
+        Ok(CacheStats {
+            enabled: settings.enabled,
+            memory_entries: self.memory.lock().expect("cache lock").entries.len(),
+            disk_entries,
+            disk_bytes,
+            max_disk_bytes: settings.max_disk_bytes,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        })
+    }
+
+    /// Empties both tiers and returns how many disk entries were removed.
+    pub fn clear(&self) -> Result<usize, CacheError> {
+        self.memory.lock().expect("cache lock").entries.clear();
+        Ok(self.disk.clear()?)
+    }
+
+    /// Applies new limits to entries that are already cached.
+    pub fn shrink(&self, settings: &CacheSettings) -> Result<(), CacheError> {
+        self.memory
+            .lock()
+            .expect("cache lock")
+            .shrink(settings.memory_entries);
+        self.disk.prune(settings.max_disk_bytes)?;
+        Ok(())
+    }
+}
+
+/// Line endings and trailing spaces do not change the translation.
+fn normalize(text: &str) -> String {
+    text.trim()
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::RetryPolicy;
+    use crate::storage::GlossaryEntry;
+
+    fn request(text: &str) -> TranslationRequest {
+        TranslationRequest {
+            text: text.to_string(),
+            source_lang: "en".to_string(),
+            target_lang: "zh".to_string(),
+            model: "model-a".to_string(),
+            stream: false,
+            request_id: None,
+            selection_source: None,
+            context: None,
+            prompt: None,
+            glossary: Vec::new(),
+            references: Vec::new(),
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    fn entry(text: &str) -> CachedTranslation {
+        CachedTranslation {
+            translated_text: text.to_string(),
+            model: "model-a".to_string(),
+        }
+    }
+
+    #[test]
+    fn keys_ignore_line_endings_and_trailing_spaces() {
+        let key = TranslationCache::key(&request("Hello\nworld"));
+        assert_eq!(
+            key,
+            TranslationCache::key(&request("  Hello  \r\nworld \n"))
+        );
+        assert_eq!(key.len(), 64);
+        assert_ne!(key, TranslationCache::key(&request("Hello world")));
+    }
+
+    #[test]
+    fn keys_depend_on_everything_that_shapes_the_output() {
+        let base = request("Hello");
+        let key = TranslationCache::key(&base);
+
+        let mut other = base.clone();
+        other.target_lang = "ja".to_string();
+        assert_ne!(TranslationCache::key(&other), key);
+
+        let mut other = base.clone();
+        other.model = "model-b".to_string();
+        assert_ne!(TranslationCache::key(&other), key);
+
+        let mut other = base.clone();
+        other.context = Some("greeting".to_string());
+        assert_ne!(TranslationCache::key(&other), key);
+
+        let mut other = base.clone();
+        other.glossary.push(GlossaryEntry {
+            source_lang: "en".to_string(),
+            target_lang: "zh".to_string(),
+            term: "Hello".to_string(),
+            translation: "您好".to_string(),
+        });
+        assert_ne!(TranslationCache::key(&other), key);
+
+        // Field boundaries are part of the hash, so shifting text between fields changes the key.
+        let mut left = base.clone();
+        left.target_lang = "zhx".to_string();
+        let mut right = base;
+        right.source_lang = "enz".to_string();
+        right.target_lang = "hx".to_string();
+        assert_ne!(TranslationCache::key(&left), TranslationCache::key(&right));
+    }
+
+    #[test]
+    fn memory_evicts_the_least_recently_used_entry() {
+        let mut memory = MemoryCache::default();
+        memory.put("a", entry("A"), 2);
+        memory.put("b", entry("B"), 2);
+        assert!(memory.get("a").is_some());
+        memory.put("c", entry("C"), 2);
+
+        assert!(memory.get("b").is_none());
+        assert_eq!(memory.get("a"), Some(entry("A")));
+        assert_eq!(memory.get("c"), Some(entry("C")));
+
+        memory.shrink(1);
+        assert_eq!(memory.entries.len(), 1);
+        assert!(memory.get("c").is_some());
+    }
+
+    #[test]
+    fn disk_hits_are_promoted_to_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = CacheSettings::default();
+        let disk = CacheStore::open(dir.path().to_path_buf());
+        disk.put("key", &entry("A")).unwrap();
+
+        let cache = TranslationCache::new(disk);
+        assert_eq!(cache.get("key", &settings), Some(entry("A")));
+        assert!(cache.get("missing", &settings).is_none());
+
+        let stats = cache.stats(&settings).unwrap();
+        assert_eq!(stats.memory_entries, 1);
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+    }
+
+    #[test]
+    fn a_disabled_disk_tier_is_not_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let disk = CacheStore::open(dir.path().to_path_buf());
+        disk.put("key", &entry("A")).unwrap();
+        let settings = CacheSettings {
+            max_disk_bytes: 0,
+            ..CacheSettings::default()
+        };
+
+        assert!(TranslationCache::new(disk).get("key", &settings).is_none());
+    }
+}
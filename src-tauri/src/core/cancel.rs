@@ -0,0 +1,223 @@
+use crate::api_client::{TranslationDelta, TranslationObserver, TranslationRetry};
+use crate::storage::BudgetWarning;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+/// Prefix of the ids given to requests that arrive without one. Requests
+/// carrying their own id may not use it, so the two never collide.
+pub const GENERATED_ID_PREFIX: &str = "tr-";
+
+/// Where a translation was started; a newer request only supersedes older
+/// ones from the same origin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RequestOrigin {
+    /// Text entered in the window with this label.
+    Window(String),
+    /// A selection reported by the selection watcher.
+    Selection,
+    /// A word looked up for the vocabulary notebook; never supersedes anything.
+    WordLookup,
+}
+
+impl RequestOrigin {
+    fn supersedes(&self) -> bool {
+        !matches!(self, Self::WordLookup)
+    }
+}
+
+struct InFlight {
+    sequence: u64,
+    origin: RequestOrigin,
+    token: CancellationToken,
+}
+
+/// Tracks running translations so they can be cancelled by request id.
+#[derive(Clone, Default)]
+pub struct InFlightRegistry {
+    next_id: Arc<AtomicU64>,
+    next_sequence: Arc<AtomicU64>,
+    requests: Arc<Mutex<HashMap<String, InFlight>>>,
+}
+
+impl InFlightRegistry {
+    pub fn next_request_id(&self) -> String {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        format!("{GENERATED_ID_PREFIX}{id}")
+    }
+
+    /// Registers a request and cancels the older requests from the same origin.
+    pub fn begin(&self, request_id: &str, origin: RequestOrigin) -> InFlightGuard {
+        let sequence = self.next_sequence.fetch_add(1, Ordering::Relaxed);
+        let token = CancellationToken::new();
+        let mut requests = self.requests.lock().expect("in-flight lock");
+
+        if origin.supersedes() {
+            for (id, in_flight) in requests.iter() {
+                if in_flight.origin == origin && in_flight.sequence < sequence {
+                    info!(request_id = %id, "cancelling superseded translation");
+                    in_flight.token.cancel();
+                }
+            }
+        }
+
+        if let Some(previous) = requests.insert(
+            request_id.to_string(),
+            InFlight {
+                sequence,
+                origin,
+                token: token.clone(),
+            },
+        ) {
+            previous.token.cancel();
+        }
+
+        InFlightGuard {
+            registry: self.clone(),
+            request_id: request_id.to_string(),
+            sequence,
+            token,
+        }
+    }
+
+    pub fn cancel(&self, request_id: &str) -> bool {
+        match self
+            .requests
+            .lock()
+            .expect("in-flight lock")
+            .get(request_id)
+        {
+            Some(in_flight) => {
+                in_flight.token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Removes its request from the registry when the translation finishes.
+pub struct InFlightGuard {
+    registry: InFlightRegistry,
+    request_id: String,
+    sequence: u64,
+    token: CancellationToken,
+}
+
+impl InFlightGuard {
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let mut requests = self.registry.requests.lock().expect("in-flight lock");
+        if requests
+            .get(&self.request_id)
+            .is_some_and(|in_flight| in_flight.sequence == self.sequence)
+        {
+            requests.remove(&self.request_id);
+        }
+    }
+}
+
+/// Drops deltas once the request they belong to has been cancelled.
+pub struct CancellableObserver<'a> {
+    pub inner: Option<&'a dyn TranslationObserver>,
+    pub token: &'a CancellationToken,
+}
+
+impl TranslationObserver for CancellableObserver<'_> {
+    fn on_delta(&self, delta: &TranslationDelta) {
+        if self.token.is_cancelled() {
+            return;
+        }
+        if let Some(inner) = self.inner {
+            inner.on_delta(delta);
+        }
+    }
+
+    fn on_retry(&self, retry: &TranslationRetry) {
+        if self.token.is_cancelled() {
+            return;
+        }
+        if let Some(inner) = self.inner {
+            inner.on_retry(retry);
+        }
+    }
+
+    fn on_budget_warning(&self, warning: &BudgetWarning) {
+        if self.token.is_cancelled() {
+            return;
+        }
+        if let Some(inner) = self.inner {
+            inner.on_budget_warning(warning);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(label: &str) -> RequestOrigin {
+        RequestOrigin::Window(label.to_string())
+    }
+
+    #[test]
+    fn a_request_supersedes_older_ones_from_its_origin_only() {
+        let registry = InFlightRegistry::default();
+        let main = registry.begin("a", window("main"));
+        let selection = registry.begin("b", RequestOrigin::Selection);
+        let other = registry.begin("c", window("other"));
+
+        let newer = registry.begin("d", window("main"));
+        assert!(main.token().is_cancelled());
+        assert!(!selection.token().is_cancelled());
+        assert!(!other.token().is_cancelled());
+        assert!(!newer.token().is_cancelled());
+    }
+
+    #[test]
+    fn word_lookups_never_supersede() {
+        let registry = InFlightRegistry::default();
+        let first = registry.begin("a", RequestOrigin::WordLookup);
+        let second = registry.begin("b", RequestOrigin::WordLookup);
+        assert!(!first.token().is_cancelled());
+        assert!(!second.token().is_cancelled());
+    }
+
+    #[test]
+    fn reusing_an_id_cancels_the_previous_request() {
+        let registry = InFlightRegistry::default();
+        let first = registry.begin("a", RequestOrigin::WordLookup);
+        let second = registry.begin("a", RequestOrigin::WordLookup);
+        assert!(first.token().is_cancelled());
+
+        // The older guard must not unregister the newer request.
+        drop(first);
+        assert!(registry.cancel("a"));
+        assert!(second.token().is_cancelled());
+    }
+
+    #[test]
+    fn finished_requests_leave_the_registry() {
+        let registry = InFlightRegistry::default();
+        let guard = registry.begin("a", RequestOrigin::Selection);
+        drop(guard);
+        assert!(!registry.cancel("a"));
+        assert!(!registry.cancel("unknown"));
+    }
+
+    #[test]
+    fn generated_ids_are_unique_and_prefixed() {
+        let registry = InFlightRegistry::default();
+        let first = registry.next_request_id();
+        let second = registry.next_request_id();
+        assert!(first.starts_with(GENERATED_ID_PREFIX));
+        assert_ne!(first, second);
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+/// Result of offline language detection, using the language names shown in the UI.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    pub lang: &'static str,
+    pub confidence: f32,
+}
+
+/// Detections below this confidence are too unsure to replace the automatic source language.
+pub const MIN_CONFIDENCE: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Script {
+    Han,
+    Kana,
+    Hangul,
+    Cyrillic,
+    Arabic,
+    Thai,
+    Latin,
+}
+
+/// Most frequent trigrams per Latin-script language, ordered by rank; `_` marks a word boundary.
+const LATIN_PROFILES: &[(&str, &[&str])] = &[
+    (
+        "English",
+        &[
+            "_th", "the", "he_", "nd_", "_an", "and", "ed_", "_of", "of_", "_to", "ing", "ng_",
+            "to_", "_in", "in_", "er_", "is_", "_is", "ion", "tio", "on_", "at_", "es_", "hat",
+            "_a_", "_ha", "for", "_fo", "or_", "ent", "_wh", "thi", "his", "ll_", "_be", "you",
+        ],
+    ),
+    (
+        "Français",
+        &[
+            "es_", "_de", "de_", "le_", "_le", "ent", "nt_", "_la", "la_", "re_", "les", "ion",
+            "_et", "et_", "que", "ue_", "_qu", "des", "_pa", "ne_", "eur", "_co", "men", "_un",
+            "est", "ait", "our", "_po", "pou", "_d'", "_l'", "ès_", "_à_", "ée_", "été", "_ce",
+        ],
+    ),
+    (
+        "Deutsch",
+        &[
+            "en_", "er_", "der", "_de", "ie_", "_di", "die", "ein", "ich", "sch", "und", "_un",
+            "nd_", "che", "ch_", "den", "gen", "cht", "_ei", "te_", "ung", "ng_", "ten", "_da",
+            "das", "ist", "_is", "nde", "ber", "_zu", "_mi", "mit", "_ni", "nic", "_ge", "ür_",
+        ],
+    ),
+    (
+        "Español",
+        &[
+            "de_", "_de", "os_", "la_", "_la", "el_", "_el", "es_", "en_", "_en", "que", "_qu",
+            "ue_", "as_", "ión", "ón_", "ent", "los", "_lo", "con", "_co", "ado", "par", "_pa",
+            "ara", "por", "_po", "del", "_y_", "una", "_un", "_es", "est", "ien", "ños", "_se",
+        ],
+    ),
+    (
+        "Italiano",
+        &[
+            "_di", "di_", "la_", "che", "_ch", "he_", "to_", "re_", "_la", "ell", "del", "lla",
+            "ion", "one", "ne_", "_co", "no_", "per", "_pe", "ent", "_il", "il_", "zio", "are",
+            "ato", "ta_", "tà_", "_de", "nte", "_e_", "_un", "gli", "_gl", "_in", "_so", "ono",
+        ],
+    ),
+    (
+        "Português",
+        &[
+            "de_", "_de", "os_", "do_", "_do", "ão_", "ção", "que", "_qu", "ue_", "da_", "_da",
+            "as_", "_co", "ent", "nte", "_a_", "em_", "_em", "com", "ra_", "par", "ara", "ado",
+            "_pa", "men", "_se", "est", "não", "_nã", "_o_", "ões", "uma", "_um", "ido", "_po",
+        ],
+    ),
+];
+
+/// Guesses the language of `text` from its scripts and, for Latin text, trigram profiles.
+///
+/// Scripts written with spaces between words count once per word and the
+/// others once per character, so a few Latin terms in Chinese text do not
+/// outweigh the Chinese around them.
+pub fn detect(text: &str) -> Option<Detection> {
+    let mut scripts: HashMap<Script, usize> = HashMap::new();
+    let mut previous = None;
+    for ch in text.chars() {
+        let script = script_of(ch);
+        if let Some(script) = script {
+            if !script.spaces_words() || previous != Some(script) {
+                *scripts.entry(script).or_default() += 1;
+            }
+        }
+        previous = script;
+    }
+
+    let total: usize = scripts.values().sum();
+    if total == 0 {
+        return None;
+    }
+    let share = |script: Script| scripts.get(&script).copied().unwrap_or(0) as f32 / total as f32;
+
+    // Japanese mixes kanji with kana; a little kana is enough to tell it apart from Chinese.
+    if share(Script::Kana) > 0.05 {
+        return Some(Detection {
+            lang: "日本語",
+            confidence: (share(Script::Kana) + share(Script::Han)).min(1.0),
+        });
+    }
+
+    let (dominant, count) = scripts
+        .iter()
+        .max_by_key(|(_, count)| **count)
+        .map(|(script, count)| (*script, *count))?;
+    let confidence = count as f32 / total as f32;
+
+    let lang = match dominant {
+        Script::Han => "中文",
+        Script::Kana => "日本語",
+        Script::Hangul => "한국어",
+        Script::Cyrillic => "Русский",
+        Script::Arabic => "العربية",
+        Script::Thai => "ไทย",
+        Script::Latin => {
+            let (lang, margin) = detect_latin(text);
+            return Some(Detection {
+                lang,
+                confidence: confidence * margin,
+            });
+        }
+    };
+
+    Some(Detection { lang, confidence })
+}
+
+/// Below this score there is too little evidence, so the detector falls back
+/// to English with a confidence under [`MIN_CONFIDENCE`].
+const MIN_LATIN_SCORE: f32 = 1.5;
+
+/// Picks the best-scoring Latin profile and a 0..=1 margin over the runner-up.
+fn detect_latin(text: &str) -> (&'static str, f32) {
+    let trigrams = trigrams(text);
+    let mut scores: Vec<(&'static str, f32)> = LATIN_PROFILES
+        .iter()
+        .map(|(lang, profile)| {
+            let score = profile
+                .iter()
+                .enumerate()
+                .map(|(rank, gram)| {
+                    let weight = 1.0 - rank as f32 / profile.len() as f32 * 0.5;
+                    trigrams.get(*gram).copied().unwrap_or(0) as f32 * weight
+                })
+                .sum();
+            (*lang, score)
+        })
+        .collect();
+    scores.sort_by(|left, right| right.1.total_cmp(&left.1));
+
+    match scores.as_slice() {
+        [(lang, best), (_, second), ..] if *best >= MIN_LATIN_SCORE => {
+            (lang, 1.0 - second / best * 0.5)
+        }
+        [(lang, best), ..] if *best >= MIN_LATIN_SCORE => (lang, 1.0),
+        _ => ("English", 0.3),
+    }
+}
+
+fn trigrams(text: &str) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for word in text
+        .split(|ch: char| !(ch.is_alphabetic() || ch == '\''))
+        .filter(|word| !word.is_empty())
+    {
+        let padded: Vec<char> = std::iter::once('_')
+            .chain(word.chars().flat_map(char::to_lowercase))
+            .chain(std::iter::once('_'))
+            .collect();
+        for window in padded.windows(3) {
+            *counts.entry(window.iter().collect::<String>()).or_default() += 1;
+        }
+    }
+    counts
+}
+
+impl Script {
+    fn spaces_words(self) -> bool {
+        matches!(self, Script::Latin | Script::Cyrillic | Script::Arabic)
+    }
+}
+
+fn script_of(ch: char) -> Option<Script> {
+    match ch {
+        '\u{3040}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}' | '\u{FF66}'..='\u{FF9F}' => {
+            Some(Script::Kana)
+        }
+        '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}' => {
+            Some(Script::Han)
+        }
+        '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}' | '\u{AC00}'..='\u{D7AF}' => {
+            Some(Script::Hangul)
+        }
+        '\u{0400}'..='\u{04FF}' => Some(Script::Cyrillic),
+        '\u{0600}'..='\u{06FF}' | '\u{0750}'..='\u{077F}' => Some(Script::Arabic),
+        '\u{0E00}'..='\u{0E7F}' => Some(Script::Thai),
+        ch if ch.is_alphabetic() && (ch.is_ascii() || ('\u{00C0}'..='\u{024F}').contains(&ch)) => {
+            Some(Script::Latin)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latin_terms_inside_chinese_stay_chinese() {
+        let detection = detect("请把 Kubernetes deployment 回滚").unwrap();
+        assert_eq!(detection.lang, "中文");
+        assert!(detection.confidence >= MIN_CONFIDENCE);
+    }
+
+    #[test]
+    fn kana_marks_japanese() {
+        assert_eq!(detect("今日は良い天気ですね").unwrap().lang, "日本語");
+    }
+
+    #[test]
+    fn latin_languages_are_told_apart() {
+        let cases = [
+            (
+                "The weather is nice and the children want to go to the park",
+                "English",
+            ),
+            (
+                "Les enfants de la maison sont partis à la plage avec leurs amis",
+                "Français",
+            ),
+            (
+                "Die Kinder sind mit dem Hund in den Park gegangen und spielen",
+                "Deutsch",
+            ),
+            (
+                "Los niños de la casa fueron a la playa con sus amigos del barrio",
+                "Español",
+            ),
+        ];
+        for (text, lang) in cases {
+            let detection = detect(text).unwrap();
+            assert_eq!(detection.lang, lang, "{text}");
+            assert!(detection.confidence >= MIN_CONFIDENCE, "{text}");
+        }
+    }
+
+    #[test]
+    fn too_little_latin_evidence_is_unsure() {
+        let detection = detect("xyz qwv").unwrap();
+        assert!(detection.confidence < MIN_CONFIDENCE);
+    }
+
+    #[test]
+    fn text_without_letters_is_undetected() {
+        assert_eq!(detect("12345 !?"), None);
+    }
+}
@@ -1,38 +1,616 @@
-use crate::api_client::{ApiClient, TranslationRequest, TranslationResponse};
+use crate::api_client::{
+    ApiError, ApiKey, KeyCheck, KeyHealth, KeyHealthTracker, ModelCaches, ModelInfo, TokenUsage,
+    TranslationDelta, TranslationDirection, TranslationObserver, TranslationProvider,
+    TranslationRequest, TranslationResponse,
+};
 use crate::platform_windows::{SelectionEvent, SelectionWatcher};
-use crate::storage::{AppSettings, CredentialStore};
+use crate::storage::{
+    app_cache_dir, app_config_dir, check_translation, AppSettings, CacheError, CacheStore,
+    CachedTranslation, CredentialError, CredentialStore, EmbeddingIndex, ExportFormat,
+    GlossaryStore, HistoryEntry, HistoryError, HistoryPage, HistoryStore, MemoryMatch,
+    NewVocabularyEntry, PromptError, SettingsError, SettingsStore, TranslationMemoryError,
+    TranslationMemoryStore, TranslationUnit, UsageStats, UsageStore, VocabularyEntry,
+    VocabularyError, VocabularyStore, AUTO_LANG, DEFAULT_DECK_NAME, MAX_EMBEDDED_CHARS,
+};
+use futures_util::StreamExt;
 use std::sync::{Arc, Mutex};
-use tauri::AppHandle;
+use tauri::{AppHandle, Manager};
 use tokio::sync::mpsc;
 use tracing::{info, warn};
 
+mod budget;
+mod cache;
+mod cancel;
+mod lang_detect;
+mod registry;
+mod segmenter;
+
+pub use cache::CacheStats;
+pub use cancel::{InFlightRegistry, RequestOrigin};
+pub use registry::ProviderRegistry;
+
+use budget::BudgetGuard;
+use cache::TranslationCache;
+use cancel::{CancellableObserver, GENERATED_ID_PREFIX};
+
+/// Past translations embedded along with each request; with the request text
+/// itself they fill one embeddings batch.
+const PENDING_EMBEDDINGS_PER_REQUEST: usize = 31;
+
 #[derive(Clone)]
 pub struct TranslatorCore {
-    api_client: ApiClient,
+    providers: Arc<Mutex<ProviderRegistry>>,
+    key_health: Arc<KeyHealthTracker>,
+    model_caches: ModelCaches,
     settings: Arc<Mutex<AppSettings>>,
+    settings_store: SettingsStore,
     credential_store: CredentialStore,
+    in_flight: InFlightRegistry,
+    glossary: GlossaryStore,
+    usage: UsageStore,
+    budget: BudgetGuard,
+    cache: TranslationCache,
+    history: HistoryStore,
+    embeddings: EmbeddingIndex,
+    vocabulary: VocabularyStore,
+    translation_memory: TranslationMemoryStore,
 }
 
 impl TranslatorCore {
     pub fn new() -> Self {
+        let settings_store = SettingsStore::new(app_config_dir().join("settings.json"));
+        let settings = settings_store.load_or_default();
+        let history = HistoryStore::open(&app_config_dir().join("history.db"));
+        let key_health = Arc::new(KeyHealthTracker::default());
+        let model_caches = ModelCaches::default();
         Self {
-            api_client: ApiClient::new(),
-            settings: Arc::new(Mutex::new(AppSettings::default())),
+            providers: Arc::new(Mutex::new(ProviderRegistry::from_settings(
+                &settings,
+                key_health.clone(),
+                &model_caches,
+            ))),
+            key_health,
+            model_caches,
+            settings: Arc::new(Mutex::new(settings)),
+            settings_store,
             credential_store: CredentialStore::new(),
+            in_flight: InFlightRegistry::default(),
+            glossary: GlossaryStore::open(app_config_dir().join("glossary.json")),
+            usage: UsageStore::open(app_config_dir().join("usage.json")),
+            budget: BudgetGuard::default(),
+            cache: TranslationCache::new(CacheStore::open(app_cache_dir().join("translations"))),
+            embeddings: history.embedding_index(),
+            history,
+            vocabulary: VocabularyStore::open(&app_config_dir().join("vocabulary.db")),
+            translation_memory: TranslationMemoryStore::open(
+                &app_config_dir().join("translation_memory.db"),
+            ),
         }
     }
 
     pub async fn translate_text(
+        &self,
+        mut request: TranslationRequest,
+        origin: RequestOrigin,
+        observer: Option<&dyn TranslationObserver>,
+    ) -> Result<TranslationResponse, ApiError> {
+        if let Some(id) = request
+            .request_id
+            .as_deref()
+            .filter(|id| id.starts_with(GENERATED_ID_PREFIX))
+        {
+            return Err(ApiError::ReservedRequestId(id.to_string()));
+        }
+        let request_id = request
+            .request_id
+            .get_or_insert_with(|| self.in_flight.next_request_id())
+            .clone();
+        let guard = self.in_flight.begin(&request_id, origin);
+        let token = guard.token();
+        let observer = CancellableObserver {
+            inner: observer,
+            token,
+        };
+
+        let settings = self.load_settings();
+        let detected = lang_detect::detect(&request.text);
+        if let Some(detection) = detected {
+            info!(
+                lang = detection.lang,
+                confidence = detection.confidence,
+                "detected source language"
+            );
+        }
+        let detected =
+            detected.filter(|detection| detection.confidence >= lang_detect::MIN_CONFIDENCE);
+        if request.source_lang == AUTO_LANG {
+            if let Some(detection) = detected {
+                request.source_lang = detection.lang.to_string();
+            }
+        }
+        let direction = resolve_direction(&mut request, &settings);
+        request.prompt = Some(settings.active_prompt());
+        request.retry_policy = settings.retry.clone();
+        request.glossary =
+            self.glossary
+                .matching(&request.text, &request.source_lang, &request.target_lang);
+        let glossary = request.glossary.clone();
+        let source_text = request.text.clone();
+        let selection_source = request.selection_source.clone();
+        let finish = |mut response: TranslationResponse| {
+            response.detected_source_lang = detected.map(|detection| detection.lang.to_string());
+            response.direction = direction.clone();
+            response.glossary_violations = check_translation(&glossary, &response.translated_text);
+            response
+        };
+
+        let stream = request.stream;
+        let serve_cached = |cached: CachedTranslation| {
+            info!(model = %cached.model, "translation served from cache");
+            if stream {
+                observer.on_delta(&TranslationDelta {
+                    request_id: Some(request_id.clone()),
+                    delta: cached.translated_text.clone(),
+                });
+            }
+            finish(TranslationResponse {
+                request_id: Some(request_id.clone()),
+                translated_text: cached.translated_text,
+                cached: true,
+                ..TranslationResponse::default()
+            })
+        };
+        // A cache hit costs nothing, so it is served before the budget check.
+        let mut cache_key = settings
+            .cache
+            .enabled
+            .then(|| TranslationCache::key(&request));
+        if let Some(cached) = cache_key
+            .as_ref()
+            .and_then(|key| self.cache.get(key, &settings.cache))
+        {
+            return Ok(serve_cached(cached));
+        }
+
+        // Roughly as many tokens come back as go out.
+        let estimated_tokens = segmenter::estimate_tokens(&request.text) as u64;
+        let estimated = TokenUsage {
+            prompt_tokens: estimated_tokens,
+            completion_tokens: estimated_tokens,
+            total_tokens: 2 * estimated_tokens,
+        };
+        let (model, _reservation) = self.budget.check(
+            &settings,
+            &self.usage,
+            &request.model,
+            &estimated,
+            Some(&request_id),
+            &observer,
+        )?;
+        if model != request.model {
+            // Downgraded: the fallback model's translation may be cached too.
+            request.model = model;
+            cache_key = settings
+                .cache
+                .enabled
+                .then(|| TranslationCache::key(&request));
+            if let Some(cached) = cache_key
+                .as_ref()
+                .and_then(|key| self.cache.get(key, &settings.cache))
+            {
+                return Ok(serve_cached(cached));
+            }
+        }
+
+        let model = request.model.clone();
+        let provider = self
+            .providers
+            .lock()
+            .expect("providers lock")
+            .get(&settings.provider)
+            .ok_or_else(|| ApiError::UnknownProvider(settings.provider.clone()))?;
+        let keys = self.credential_store.api_keys().unwrap_or_else(|err| {
+            warn!(%err, "failed to read api keys");
+            Vec::new()
+        });
+
+        tokio::select! {
+            biased;
+            _ = token.cancelled() => Err(ApiError::Cancelled),
+            result = async {
+                self.retrieve_examples(provider.as_ref(), &keys, &settings, &mut request, &observer).await;
+                translate_segmented(provider.as_ref(), request, &keys, &settings, &self.usage, &self.translation_memory, &observer)
+                    .await
+            } => {
+                if token.is_cancelled() {
+                    return Err(ApiError::Cancelled);
+                }
+                let response = result?;
+                if let Some(key) = &cache_key {
+                    let entry = CachedTranslation {
+                        translated_text: response.translated_text.clone(),
+                        model: model.clone(),
+                    };
+                    self.cache.put(key, entry, &settings.cache);
+                }
+                // Cache hits were recorded when they were first translated.
+                if settings.history_enabled {
+                    let entry = HistoryEntry {
+                        id: 0,
+                        source_text,
+                        translated_text: response.translated_text.clone(),
+                        source_lang: direction.source_lang.clone(),
+                        target_lang: direction.target_lang.clone(),
+                        model: model.clone(),
+                        selection_source,
+                        created_at: chrono::Utc::now().timestamp_millis(),
+                    };
+                    let history = self.history.clone();
+                    tokio::task::spawn_blocking(move || {
+                        if let Err(err) = history.record(&entry) {
+                            warn!(%err, "failed to record translation history");
+                        }
+                    });
+                }
+                Ok(finish(response))
+            }
+        }
+    }
+
+    /// Translates a word for the vocabulary notebook without cancelling or
+    /// being cancelled by the translations on screen.
+    pub async fn lookup_word(
         &self,
         request: TranslationRequest,
-    ) -> Result<TranslationResponse, crate::api_client::ApiError> {
-        let api_key = self.credential_store.read_api_key().ok();
-        self.api_client.translate(request, api_key).await
+    ) -> Result<TranslationResponse, ApiError> {
+        let request = TranslationRequest {
+            stream: false,
+            selection_source: None,
+            ..request
+        };
+        self.translate_text(request, RequestOrigin::WordLookup, None)
+            .await
+    }
+
+    /// Fills `request.references` with the past translations closest in meaning
+    /// to `request.text`. Past texts that were never embedded are sent in the
+    /// same call, and every new vector is stored.
+    async fn retrieve_examples(
+        &self,
+        provider: &dyn TranslationProvider,
+        keys: &[ApiKey],
+        settings: &AppSettings,
+        request: &mut TranslationRequest,
+        observer: &dyn TranslationObserver,
+    ) {
+        let semantic = &settings.semantic_retrieval;
+        if !semantic.enabled || request.text.chars().count() > MAX_EMBEDDED_CHARS {
+            return;
+        }
+        let embeddings = self.embeddings.clone();
+        let (model, text) = (semantic.model.clone(), request.text.clone());
+        let lookup = tokio::task::spawn_blocking(move || {
+            let known = embeddings.vector(&model, &text).unwrap_or_else(|err| {
+                warn!(%err, "failed to read the embedding index");
+                None
+            });
+            let mut inputs = embeddings
+                .pending(&model, PENDING_EMBEDDINGS_PER_REQUEST)
+                .unwrap_or_default();
+            inputs.retain(|pending| *pending != text);
+            if known.is_none() {
+                inputs.insert(0, text);
+            }
+            (known, inputs)
+        })
+        .await;
+        let (known, inputs) = match lookup {
+            Ok(lookup) => lookup,
+            Err(err) => {
+                warn!(%err, "embedding index lookup panicked");
+                return;
+            }
+        };
+
+        let vectors = if inputs.is_empty() {
+            Vec::new()
+        } else {
+            let prompt_tokens = inputs
+                .iter()
+                .map(|input| segmenter::estimate_tokens(input) as u64)
+                .sum();
+            let tokens = TokenUsage {
+                prompt_tokens,
+                completion_tokens: 0,
+                total_tokens: prompt_tokens,
+            };
+            // A downgrade names a chat model, which cannot embed, so it skips retrieval too.
+            let _reservation = match self.budget.check(
+                settings,
+                &self.usage,
+                &semantic.model,
+                &tokens,
+                request.request_id.as_deref(),
+                observer,
+            ) {
+                Ok((model, reservation)) if model == semantic.model => reservation,
+                Ok(_) | Err(_) => {
+                    info!("budget exhausted, translating without semantic examples");
+                    return;
+                }
+            };
+            match provider
+                .embed(&inputs, &semantic.model, keys, &settings.retry)
+                .await
+            {
+                Ok(vectors) => {
+                    // Embedding responses carry no key name, so usage is filed like unreported keys.
+                    self.usage
+                        .record(&semantic.model, "unknown", &tokens, &settings.model_prices);
+                    vectors
+                }
+                Err(err) => {
+                    warn!(%err, "embedding request failed, translating without semantic examples");
+                    return;
+                }
+            }
+        };
+        let Some(vector) = known.or_else(|| vectors.first().cloned()) else {
+            return;
+        };
+        let indexed: Vec<(String, Vec<f32>)> = inputs.into_iter().zip(vectors).collect();
+
+        let embeddings = self.embeddings.clone();
+        let semantic = semantic.clone();
+        let (source_lang, target_lang) = (request.source_lang.clone(), request.target_lang.clone());
+        let examples = tokio::task::spawn_blocking(move || {
+            if !indexed.is_empty() {
+                info!(count = indexed.len(), "stored new embeddings");
+                if let Err(err) = embeddings.store(&semantic.model, &indexed) {
+                    warn!(%err, "failed to store embeddings");
+                }
+            }
+            embeddings.nearest(&vector, &source_lang, &target_lang, &semantic)
+        })
+        .await;
+        match examples {
+            Ok(Ok(examples)) => request.references = examples,
+            Ok(Err(err)) => warn!(%err, "semantic example lookup failed"),
+            Err(err) => warn!(%err, "semantic example lookup panicked"),
+        }
+    }
+
+    /// Tests the named key, or the first stored key, against the active provider.
+    pub async fn test_api_key(&self, name: Option<&str>) -> Result<KeyCheck, CredentialError> {
+        let keys = self.credential_store.api_keys()?;
+        let key = match name {
+            Some(name) => keys.into_iter().find(|key| key.name == name),
+            None => keys.into_iter().next(),
+        };
+        let result = match (self.active_provider(), key) {
+            (Err(err), _) => Err(err),
+            (Ok(_), None) => Err(ApiError::MissingApiKey),
+            (Ok(provider), Some(key)) => provider.check_key(&key.secret).await,
+        };
+        Ok(KeyCheck::from(result))
+    }
+
+    /// Cooldown state of every stored key for the active provider.
+    pub fn key_health(&self) -> Result<Vec<KeyHealth>, CredentialError> {
+        let keys = self.credential_store.api_keys()?;
+        Ok(match self.active_provider() {
+            Ok(provider) => provider.key_health(&keys),
+            Err(_) => Vec::new(),
+        })
+    }
+
+    /// Chat models offered by the active provider, fetched with the first stored key.
+    pub async fn list_models(&self, refresh: bool) -> Result<Vec<ModelInfo>, ApiError> {
+        let provider = self.active_provider()?;
+        let keys = self.credential_store.api_keys().unwrap_or_else(|err| {
+            warn!(%err, "failed to read api keys");
+            Vec::new()
+        });
+        let key = keys.first().ok_or(ApiError::MissingApiKey)?;
+        provider.list_models(&key.secret, refresh).await
+    }
+
+    pub fn usage_stats(&self) -> UsageStats {
+        self.usage.stats()
+    }
+
+    pub fn list_history(&self, offset: u32, limit: u32) -> Result<HistoryPage, HistoryError> {
+        self.history.list(offset, limit)
+    }
+
+    pub fn search_history(
+        &self,
+        query: &str,
+        offset: u32,
+        limit: u32,
+    ) -> Result<HistoryPage, HistoryError> {
+        self.history.search(query, offset, limit)
+    }
+
+    pub fn delete_history(&self, ids: &[i64]) -> Result<usize, HistoryError> {
+        self.history.delete(ids)
+    }
+
+    pub fn clear_history(&self) -> Result<usize, HistoryError> {
+        self.history.clear()
+    }
+
+    pub fn export_history(&self, format: ExportFormat) -> Result<String, HistoryError> {
+        self.history.export(format)
+    }
+
+    pub fn star_word(
+        &self,
+        entry: &NewVocabularyEntry,
+    ) -> Result<VocabularyEntry, VocabularyError> {
+        self.vocabulary
+            .add(entry, chrono::Utc::now().timestamp_millis())
+    }
+
+    pub fn list_vocabulary(&self) -> Result<Vec<VocabularyEntry>, VocabularyError> {
+        self.vocabulary.entries()
+    }
+
+    pub fn due_vocabulary(&self, limit: u32) -> Result<Vec<VocabularyEntry>, VocabularyError> {
+        self.vocabulary
+            .due(chrono::Utc::now().timestamp_millis(), limit)
+    }
+
+    pub fn review_vocabulary(
+        &self,
+        id: i64,
+        grade: u8,
+    ) -> Result<VocabularyEntry, VocabularyError> {
+        self.vocabulary
+            .review(id, grade, chrono::Utc::now().timestamp_millis())
+    }
+
+    pub fn remove_vocabulary(&self, id: i64) -> Result<(), VocabularyError> {
+        if self.vocabulary.remove(id)? {
+            Ok(())
+        } else {
+            Err(VocabularyError::NotFound(id))
+        }
+    }
+
+    pub fn export_vocabulary_tsv(&self) -> Result<String, VocabularyError> {
+        self.vocabulary.export_tsv()
+    }
+
+    pub fn export_vocabulary_apkg(
+        &self,
+        deck_name: Option<&str>,
+    ) -> Result<Vec<u8>, VocabularyError> {
+        let deck_name = deck_name
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .unwrap_or(DEFAULT_DECK_NAME);
+        self.vocabulary
+            .export_apkg(deck_name, chrono::Utc::now().timestamp_millis())
     }
 
-    pub fn update_settings(&self, settings: AppSettings) {
+    /// Adds the units of a TMX document and returns how many were stored.
+    pub fn import_tmx(&self, xml: &str) -> Result<usize, TranslationMemoryError> {
+        let imported = self
+            .translation_memory
+            .import_tmx(xml, chrono::Utc::now().timestamp_millis())?;
+        info!(imported, "imported translation memory");
+        Ok(imported)
+    }
+
+    pub fn export_tmx(&self) -> Result<String, TranslationMemoryError> {
+        self.translation_memory.export_tmx()
+    }
+
+    pub fn translation_memory_size(&self) -> Result<u64, TranslationMemoryError> {
+        self.translation_memory.count()
+    }
+
+    pub fn clear_translation_memory(&self) -> Result<usize, TranslationMemoryError> {
+        self.translation_memory.clear()
+    }
+
+    pub fn cache_stats(&self) -> Result<CacheStats, CacheError> {
+        self.cache.stats(&self.load_settings().cache)
+    }
+
+    pub fn clear_cache(&self) -> Result<usize, CacheError> {
+        let removed = self.cache.clear()?;
+        info!(removed, "cleared translation cache");
+        Ok(removed)
+    }
+
+    /// Saves new cache limits and evicts entries beyond them.
+    pub fn set_cache_limits(
+        &self,
+        memory_entries: usize,
+        max_disk_bytes: u64,
+    ) -> Result<(), SettingsError> {
+        let mut settings = self.load_settings();
+        settings.cache.memory_entries = memory_entries;
+        settings.cache.max_disk_bytes = max_disk_bytes;
+        self.update_settings(settings.clone())?;
+        if let Err(err) = self.cache.shrink(&settings.cache) {
+            warn!(%err, "failed to shrink translation cache");
+        }
+        Ok(())
+    }
+
+    fn active_provider(&self) -> Result<Arc<dyn TranslationProvider>, ApiError> {
+        let name = self.load_settings().provider;
+        self.providers
+            .lock()
+            .expect("providers lock")
+            .get(&name)
+            .ok_or(ApiError::UnknownProvider(name))
+    }
+
+    pub fn cancel_translation(&self, request_id: &str) -> bool {
+        self.in_flight.cancel(request_id)
+    }
+
+    /// Validates and persists `settings`, and only then makes them current.
+    ///
+    /// A newly chosen model is checked against the model list cached for the
+    /// new provider and base URL; nothing is fetched here, so saving works offline.
+    pub fn update_settings(&self, settings: AppSettings) -> Result<(), SettingsError> {
+        let previous = self.load_settings();
+        let rebuilt = ProviderRegistry::needs_rebuild(&previous, &settings)
+            .then(|| {
+                ProviderRegistry::try_from_settings(
+                    &settings,
+                    self.key_health.clone(),
+                    &self.model_caches,
+                )
+            })
+            .transpose()
+            // Field errors explain a bad proxy or header better than the builder.
+            .map_err(|err| match settings.validate(None) {
+                Err(invalid) => SettingsError::from(invalid),
+                Ok(()) => err.into(),
+            })?;
+        let changed_model = settings.model != previous.model || rebuilt.is_some();
+        let available = changed_model
+            .then(|| match &rebuilt {
+                Some(registry) => registry.get(&settings.provider),
+                None => self
+                    .providers
+                    .lock()
+                    .expect("providers lock")
+                    .get(&settings.provider),
+            })
+            .flatten()
+            .and_then(|provider| provider.cached_models());
+        settings.validate(available.as_deref())?;
+        self.settings_store.save(&settings)?;
+        if let Some(registry) = rebuilt {
+            *self.providers.lock().expect("providers lock") = registry;
+        }
         let mut current = self.settings.lock().expect("settings lock");
         *current = settings;
+        Ok(())
+    }
+
+    pub fn select_prompt_profile(&self, name: &str) -> Result<(), SettingsError> {
+        let mut settings = self.load_settings();
+        if !settings
+            .prompt_profiles
+            .iter()
+            .any(|profile| profile.name == name)
+        {
+            return Err(PromptError::UnknownProfile(name.to_string()).into());
+        }
+        settings.active_prompt_profile = name.to_string();
+        self.update_settings(settings)
+    }
+
+    pub fn provider_names(&self) -> Vec<String> {
+        self.providers.lock().expect("providers lock").names()
     }
 
     pub fn load_settings(&self) -> AppSettings {
@@ -43,6 +621,10 @@ impl TranslatorCore {
         &self.credential_store
     }
 
+    pub fn glossary(&self) -> &GlossaryStore {
+        &self.glossary
+    }
+
     pub fn start_selection_watch(&self, app: &AppHandle) {
         let (tx, mut rx) = mpsc::channel::<SelectionEvent>(32);
         SelectionWatcher::spawn(tx);
@@ -59,6 +641,265 @@ impl TranslatorCore {
     }
 }
 
+/// Switches to the secondary target when the source is already in the target language.
+fn resolve_direction(
+    request: &mut TranslationRequest,
+    settings: &AppSettings,
+) -> TranslationDirection {
+    let same_language = |left: &str, right: &str| left.trim().eq_ignore_ascii_case(right.trim());
+    let swapped = request.source_lang != AUTO_LANG
+        && same_language(&request.source_lang, &request.target_lang)
+        && !same_language(&request.source_lang, &settings.secondary_target_lang);
+
+    if swapped {
+        info!(
+            source = %request.source_lang,
+            target = %settings.secondary_target_lang,
+            "source matches target, using secondary target language"
+        );
+        request.target_lang = settings.secondary_target_lang.clone();
+    }
+
+    TranslationDirection {
+        source_lang: request.source_lang.clone(),
+        target_lang: request.target_lang.clone(),
+        swapped,
+    }
+}
+
+/// Translates `request` in one call, or segment by segment when it exceeds the token budget.
+async fn translate_segmented(
+    provider: &dyn TranslationProvider,
+    request: TranslationRequest,
+    keys: &[ApiKey],
+    settings: &AppSettings,
+    usage: &UsageStore,
+    memory: &TranslationMemoryStore,
+    observer: &dyn TranslationObserver,
+) -> Result<TranslationResponse, ApiError> {
+    let segments = segmenter::split(&request.text, settings.segment_token_budget);
+    if segments.len() <= 1 {
+        let model = request.model.clone();
+        let references = memory_references(memory, settings, &request, vec![request.text.clone()])
+            .await
+            .pop()
+            .unwrap_or_default();
+        let response = provider
+            .translate(
+                TranslationRequest {
+                    references,
+                    ..request.clone()
+                },
+                keys,
+                Some(observer),
+            )
+            .await?;
+        record_usage(usage, settings, &model, &request.text, &response);
+        remember(
+            memory,
+            settings,
+            &request,
+            &request.text,
+            &response.translated_text,
+        );
+        return Ok(response);
+    }
+
+    info!(
+        segments = segments.len(),
+        "translating long text in segments"
+    );
+    let request_id = request.request_id.clone();
+    let model = request.model.clone();
+    let texts = segments
+        .iter()
+        .map(|segment| segment.text.clone())
+        .collect();
+    let references = memory_references(memory, settings, &request, texts).await;
+    let segment_requests: Vec<TranslationRequest> = segments
+        .iter()
+        .zip(references)
+        .map(|(segment, references)| TranslationRequest {
+            text: segment.text.clone(),
+            stream: false,
+            references,
+            ..request.clone()
+        })
+        .collect();
+    let mut results =
+        futures_util::stream::iter(segment_requests.into_iter().map(|segment_request| {
+            translate_with_retry(
+                provider,
+                segment_request,
+                keys,
+                settings.segment_max_retries,
+                observer,
+            )
+        }))
+        .buffered(settings.segment_concurrency.max(1));
+
+    let mut translations = Vec::with_capacity(segments.len());
+    let mut total_usage = None;
+    while let Some(result) = results.next().await {
+        let response = result?;
+        let segment = &segments[translations.len()];
+        record_usage(usage, settings, &model, &segment.text, &response);
+        if let Some(segment_usage) = &response.usage {
+            total_usage
+                .get_or_insert_with(TokenUsage::default)
+                .add(segment_usage);
+        }
+        remember(
+            memory,
+            settings,
+            &request,
+            &segment.text,
+            &response.translated_text,
+        );
+        if request.stream {
+            observer.on_delta(&TranslationDelta {
+                request_id: request_id.clone(),
+                delta: format!("{}{}", response.translated_text.trim(), segment.separator),
+            });
+        }
+        translations.push(response.translated_text);
+    }
+
+    Ok(TranslationResponse {
+        request_id,
+        translated_text: segmenter::reassemble(&segments, translations.iter().map(String::as_str)),
+        usage: total_usage,
+        ..TranslationResponse::default()
+    })
+}
+
+/// For each of `texts`, `request.references` followed by earlier translations
+/// of similar segments.
+///
+/// The lookups run on the blocking pool: each one queries SQLite and scores
+/// candidates by edit distance.
+async fn memory_references(
+    memory: &TranslationMemoryStore,
+    settings: &AppSettings,
+    request: &TranslationRequest,
+    texts: Vec<String>,
+) -> Vec<Vec<MemoryMatch>> {
+    let count = texts.len();
+    if !settings.translation_memory.enabled || request.source_lang == AUTO_LANG {
+        return vec![request.references.clone(); count];
+    }
+    let memory = memory.clone();
+    let memory_settings = settings.translation_memory.clone();
+    let (source_lang, target_lang) = (request.source_lang.clone(), request.target_lang.clone());
+    let lookups = tokio::task::spawn_blocking(move || {
+        texts
+            .iter()
+            .map(|text| {
+                memory
+                    .lookup(text, &source_lang, &target_lang, &memory_settings)
+                    .unwrap_or_else(|err| {
+                        warn!(%err, "translation memory lookup failed");
+                        Vec::new()
+                    })
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_else(|err| {
+        warn!(%err, "translation memory lookup panicked");
+        vec![Vec::new(); count]
+    });
+
+    lookups
+        .into_iter()
+        .map(|matches| {
+            let mut references = request.references.clone();
+            for candidate in matches {
+                if !references
+                    .iter()
+                    .any(|reference| reference.source_text == candidate.source_text)
+                {
+                    references.push(candidate);
+                }
+            }
+            references
+        })
+        .collect()
+}
+
+fn remember(
+    memory: &TranslationMemoryStore,
+    settings: &AppSettings,
+    request: &TranslationRequest,
+    source_text: &str,
+    translation: &str,
+) {
+    // Units filed under the automatic language would never match a lookup.
+    if !settings.translation_memory.enabled || request.source_lang == AUTO_LANG {
+        return;
+    }
+    let unit = TranslationUnit {
+        source_text: source_text.to_string(),
+        target_text: translation.to_string(),
+        source_lang: request.source_lang.clone(),
+        target_lang: request.target_lang.clone(),
+        updated_at: chrono::Utc::now().timestamp_millis(),
+    };
+    if let Err(err) = memory.record(&unit) {
+        warn!(%err, "failed to record translation memory");
+    }
+}
+
+/// Records the tokens the provider reported, or an estimate from `source`
+/// and the translation when it reported none.
+fn record_usage(
+    usage: &UsageStore,
+    settings: &AppSettings,
+    model: &str,
+    source: &str,
+    response: &TranslationResponse,
+) {
+    let tokens = response.usage.unwrap_or_else(|| {
+        let prompt_tokens = segmenter::estimate_tokens(source) as u64;
+        let completion_tokens = segmenter::estimate_tokens(&response.translated_text) as u64;
+        TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    });
+    let key = response.api_key_name.as_deref().unwrap_or("unknown");
+    usage.record(model, key, &tokens, &settings.model_prices);
+}
+
+/// Translates one segment, asking again when the provider returns nothing.
+/// Transport and server errors are already retried by the provider under the
+/// `RetryPolicy`, so they are passed through.
+async fn translate_with_retry(
+    provider: &dyn TranslationProvider,
+    request: TranslationRequest,
+    keys: &[ApiKey],
+    max_retries: u32,
+    observer: &dyn TranslationObserver,
+) -> Result<TranslationResponse, ApiError> {
+    let mut attempt = 0;
+    loop {
+        let response = provider
+            .translate(request.clone(), keys, Some(observer))
+            .await?;
+        if !response.translated_text.trim().is_empty() || request.text.trim().is_empty() {
+            return Ok(response);
+        }
+        if attempt >= max_retries {
+            return Err(ApiError::Unexpected(
+                "empty translation for a segment".to_string(),
+            ));
+        }
+        attempt += 1;
+        warn!(attempt, "segment came back empty, retrying");
+    }
+}
+
 pub struct AppState {
     core: TranslatorCore,
 }
@@ -0,0 +1,102 @@
+use crate::api_client::{
+    build_http_client, ApiClient, HttpConfigError, KeyHealthTracker, ModelCaches,
+    TranslationProvider,
+};
+use crate::storage::{AppSettings, NetworkSettings};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Translation backends addressable by the name stored in `AppSettings::provider`.
+#[derive(Clone)]
+pub struct ProviderRegistry {
+    providers: HashMap<String, Arc<dyn TranslationProvider>>,
+}
+
+impl ProviderRegistry {
+    /// Builds the built-in and custom providers with the configured network
+    /// options; they all record key failures in `key_health` and keep their
+    /// model lists in `model_caches`.
+    pub fn try_from_settings(
+        settings: &AppSettings,
+        key_health: Arc<KeyHealthTracker>,
+        model_caches: &ModelCaches,
+    ) -> Result<Self, HttpConfigError> {
+        let http = build_http_client(&settings.network)?;
+        Ok(Self::with_http_client(
+            settings,
+            http,
+            key_health,
+            model_caches,
+        ))
+    }
+
+    /// Like `try_from_settings`, but falls back to the default network
+    /// options when the client cannot be built, so startup never fails.
+    pub fn from_settings(
+        settings: &AppSettings,
+        key_health: Arc<KeyHealthTracker>,
+        model_caches: &ModelCaches,
+    ) -> Self {
+        let http = build_http_client(&settings.network).unwrap_or_else(|err| {
+            warn!(%err, "network settings cannot be applied, using defaults");
+            build_http_client(&NetworkSettings::default()).expect("default http client")
+        });
+        Self::with_http_client(settings, http, key_health, model_caches)
+    }
+
+    fn with_http_client(
+        settings: &AppSettings,
+        http: reqwest::Client,
+        key_health: Arc<KeyHealthTracker>,
+        model_caches: &ModelCaches,
+    ) -> Self {
+        let mut clients = vec![ApiClient::new(), ApiClient::openai()];
+        for config in &settings.custom_providers {
+            if clients.iter().any(|client| client.name() == config.name) {
+                warn!(name = %config.name, "custom provider overrides an existing one");
+            }
+            clients.push(ApiClient::with_endpoint(
+                config.name.clone(),
+                config.endpoint.clone(),
+            ));
+        }
+
+        let mut registry = Self {
+            providers: HashMap::new(),
+        };
+        for client in clients {
+            let mut client = client
+                .with_http_client(http.clone())
+                .with_key_health(key_health.clone());
+            if let Some(base_url) = settings.network.base_url() {
+                if client.name() == settings.provider {
+                    client = client.with_base_url(base_url);
+                }
+            }
+            registry.register(Arc::new(client.with_model_caches(model_caches)));
+        }
+        registry
+    }
+
+    /// Whether moving from `previous` to `settings` changes how providers are built.
+    pub fn needs_rebuild(previous: &AppSettings, settings: &AppSettings) -> bool {
+        settings.provider != previous.provider
+            || settings.custom_providers != previous.custom_providers
+            || settings.network != previous.network
+    }
+
+    pub fn register(&mut self, provider: Arc<dyn TranslationProvider>) {
+        self.providers.insert(provider.name().to_string(), provider);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn TranslationProvider>> {
+        self.providers.get(name).cloned()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.providers.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
@@ -0,0 +1,328 @@
+/// A piece of the source text that is translated on its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub text: String,
+    /// Whitespace that followed the segment in the source, restored on reassembly.
+    pub separator: String,
+}
+
+const SENTENCE_TERMINATORS: &[char] = &['.', '!', '?', ';', '。', '！', '？', '；', '…'];
+const CLOSING_MARKS: &[char] = &['"', '\'', ')', ']', '”', '’', '）', '」', '』', '》'];
+
+/// Rough token count: one token per CJK character, one per four other characters.
+pub fn estimate_tokens(text: &str) -> usize {
+    let mut wide = 0;
+    let mut narrow = 0_usize;
+    for ch in text.chars() {
+        if is_wide(ch) {
+            wide += 1;
+        } else {
+            narrow += 1;
+        }
+    }
+    wide + narrow.div_ceil(4)
+}
+
+/// Splits `text` on paragraph and then sentence boundaries so that every segment fits `budget`.
+pub fn split(text: &str, budget: usize) -> Vec<Segment> {
+    let budget = budget.max(1);
+    let text = text.trim();
+    if estimate_tokens(text) <= budget {
+        return vec![Segment {
+            text: text.to_string(),
+            separator: String::new(),
+        }];
+    }
+
+    let mut segments = Vec::new();
+    for (paragraph, separator) in paragraphs(text) {
+        if estimate_tokens(paragraph) <= budget {
+            segments.push(Segment {
+                text: paragraph.to_string(),
+                separator: separator.to_string(),
+            });
+            continue;
+        }
+
+        let mut pieces = pack_sentences(paragraph, budget);
+        if let Some(last) = pieces.last_mut() {
+            last.separator = separator.to_string();
+        }
+        segments.extend(pieces);
+    }
+    segments
+}
+
+/// Joins translated segments back together with the original separators.
+pub fn reassemble<'a>(
+    segments: &[Segment],
+    translations: impl IntoIterator<Item = &'a str>,
+) -> String {
+    segments
+        .iter()
+        .zip(translations)
+        .map(|(segment, translation)| format!("{}{}", translation.trim(), segment.separator))
+        .collect()
+}
+
+/// Yields each paragraph together with the blank-line run that follows it.
+fn paragraphs(text: &str) -> Vec<(&str, &str)> {
+    let mut result = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    let mut blank_run: Option<usize> = None;
+
+    for line in text.split_inclusive('\n') {
+        let is_blank = line.trim().is_empty();
+        match (is_blank, blank_run) {
+            (true, None) => blank_run = Some(offset),
+            (false, Some(run_start)) => {
+                let paragraph_end = text[..run_start].trim_end().len();
+                result.push((&text[start..paragraph_end], &text[paragraph_end..offset]));
+                start = offset;
+                blank_run = None;
+            }
+            _ => {}
+        }
+        offset += line.len();
+    }
+
+    result.push((&text[start..], ""));
+    result
+}
+
+/// Greedily groups sentences of one paragraph into segments under `budget`.
+fn pack_sentences(paragraph: &str, budget: usize) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut current = String::new();
+    let mut current_separator = String::new();
+
+    for (sentence, separator) in sentences(paragraph) {
+        let pieces = hard_split(sentence, budget);
+        let count = pieces.len();
+        for (index, piece) in pieces.into_iter().enumerate() {
+            let text = piece.trim_end();
+            let trailing = if index + 1 == count {
+                separator
+            } else {
+                &piece[text.len()..]
+            };
+            let candidate = format!("{current}{current_separator}{text}");
+            if !current.is_empty() && estimate_tokens(&candidate) > budget {
+                segments.push(Segment {
+                    text: std::mem::take(&mut current),
+                    separator: std::mem::take(&mut current_separator),
+                });
+                current = text.to_string();
+            } else {
+                current = candidate;
+            }
+            current_separator = trailing.to_string();
+        }
+    }
+
+    if !current.is_empty() {
+        segments.push(Segment {
+            text: current,
+            separator: current_separator,
+        });
+    }
+    segments
+}
+
+/// Splits a paragraph after sentence terminators, keeping the whitespace that follows each one.
+fn sentences(paragraph: &str) -> Vec<(&str, &str)> {
+    let mut result = Vec::new();
+    let mut start = 0;
+    let mut chars = paragraph.char_indices().peekable();
+
+    while let Some((_, ch)) = chars.next() {
+        if !SENTENCE_TERMINATORS.contains(&ch) {
+            continue;
+        }
+        while let Some(&(_, next)) = chars.peek() {
+            if SENTENCE_TERMINATORS.contains(&next) || CLOSING_MARKS.contains(&next) {
+                chars.next();
+            } else {
+                break;
+            }
+        }
+
+        let end = chars.peek().map_or(paragraph.len(), |(index, _)| *index);
+        let mut separator_end = end;
+        while let Some(&(index, next)) = chars.peek() {
+            if !next.is_whitespace() {
+                break;
+            }
+            chars.next();
+            separator_end = index + next.len_utf8();
+        }
+
+        // A Latin full stop only ends a sentence when whitespace follows it.
+        if ch == '.' && separator_end == end && end < paragraph.len() {
+            continue;
+        }
+
+        result.push((&paragraph[start..end], &paragraph[end..separator_end]));
+        start = separator_end;
+    }
+
+    if start < paragraph.len() {
+        result.push((&paragraph[start..], ""));
+    }
+    result
+}
+
+/// Last resort for a single sentence over budget: cut on whitespace, or anywhere for CJK.
+fn hard_split(sentence: &str, budget: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = sentence;
+
+    while estimate_tokens(rest) > budget {
+        let mut wide = 0;
+        let mut narrow = 0_usize;
+        let mut cut = 0;
+        let mut last_space = None;
+        for (index, ch) in rest.char_indices() {
+            if is_wide(ch) {
+                wide += 1;
+            } else {
+                narrow += 1;
+            }
+            if wide + narrow.div_ceil(4) > budget {
+                break;
+            }
+            cut = index + ch.len_utf8();
+            if ch.is_whitespace() {
+                last_space = Some(cut);
+            }
+        }
+        let cut = match last_space {
+            Some(space) => space,
+            None if cut > 0 => cut,
+            None => rest.chars().next().map_or(rest.len(), char::len_utf8),
+        };
+        pieces.push(&rest[..cut]);
+        rest = &rest[cut..];
+    }
+
+    if !rest.is_empty() {
+        pieces.push(rest);
+    }
+    pieces
+}
+
+fn is_wide(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{2E80}'..='\u{9FFF}' | '\u{AC00}'..='\u{D7AF}' | '\u{F900}'..='\u{FAFF}' | '\u{FF00}'..='\u{FFEF}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(segments: &[Segment]) -> Vec<&str> {
+        segments
+            .iter()
+            .map(|segment| segment.text.as_str())
+            .collect()
+    }
+
+    /// Reassembling the untranslated segments must give back the source.
+    fn assert_round_trip(text: &str, budget: usize) -> Vec<Segment> {
+        let segments = split(text, budget);
+        assert_eq!(reassemble(&segments, texts(&segments)), text.trim());
+        for segment in &segments {
+            assert!(
+                estimate_tokens(&segment.text) <= budget,
+                "{:?} is over {budget} tokens",
+                segment.text
+            );
+        }
+        segments
+    }
+
+    #[test]
+    fn estimates_cjk_per_character_and_latin_per_four() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+        assert_eq!(estimate_tokens("你好"), 2);
+        assert_eq!(estimate_tokens("你好 ab"), 3);
+    }
+
+    #[test]
+    fn text_under_budget_stays_whole() {
+        let segments = split("  One sentence. Another one.\n", 100);
+        assert_eq!(
+            segments,
+            vec![Segment {
+                text: "One sentence. Another one.".to_string(),
+                separator: String::new(),
+            }]
+        );
+    }
+
+    #[test]
+    fn paragraphs_keep_their_blank_lines() {
+        let text = "First paragraph here.\n\n\nSecond one.";
+        let segments = assert_round_trip(text, 6);
+        assert_eq!(texts(&segments), ["First paragraph here.", "Second one."]);
+        assert_eq!(segments[0].separator, "\n\n\n");
+    }
+
+    #[test]
+    fn sentences_are_packed_up_to_the_budget() {
+        let text = "One two. Three four. Five six seven eight nine.";
+        let segments = assert_round_trip(text, 7);
+        assert_eq!(
+            texts(&segments),
+            ["One two. Three four.", "Five six seven eight nine."]
+        );
+    }
+
+    #[test]
+    fn full_stop_without_whitespace_does_not_end_a_sentence() {
+        let segments = assert_round_trip("Version 1.2.3 is out. Update now.", 6);
+        assert_eq!(texts(&segments), ["Version 1.2.3 is out.", "Update now."]);
+    }
+
+    #[test]
+    fn cjk_sentences_split_without_whitespace() {
+        let segments = assert_round_trip("今天天气很好。我们去公园吧！", 8);
+        assert_eq!(texts(&segments), ["今天天气很好。", "我们去公园吧！"]);
+        assert!(segments.iter().all(|segment| segment.separator.is_empty()));
+    }
+
+    #[test]
+    fn cjk_without_punctuation_is_cut_anywhere() {
+        let segments = assert_round_trip("一二三四五六七八九十", 3);
+        assert_eq!(texts(&segments), ["一二三", "四五六", "七八九", "十"]);
+    }
+
+    #[test]
+    fn long_latin_sentence_is_cut_on_whitespace() {
+        let segments = assert_round_trip("The quick brown fox jumps over the lazy dog", 3);
+        assert!(segments.len() > 1);
+        assert!(segments
+            .iter()
+            .all(|segment| !segment.text.starts_with(' ') && !segment.text.ends_with(' ')));
+    }
+
+    #[test]
+    fn reassemble_trims_translations_and_restores_separators() {
+        let segments = vec![
+            Segment {
+                text: "a".to_string(),
+                separator: "\n\n".to_string(),
+            },
+            Segment {
+                text: "b".to_string(),
+                separator: String::new(),
+            },
+        ];
+        assert_eq!(reassemble(&segments, [" A \n", "B "]), "A\n\nB");
+    }
+}
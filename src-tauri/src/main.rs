@@ -57,10 +57,43 @@ fn main() {
         })
         .invoke_handler(tauri::generate_handler![
             AppCommands::translate,
+            AppCommands::lookup_word,
+            AppCommands::cancel_translation,
             AppCommands::save_settings,
             AppCommands::load_settings,
+            AppCommands::select_prompt_profile,
+            AppCommands::list_providers,
+            AppCommands::import_glossary,
+            AppCommands::list_glossary,
+            AppCommands::remove_glossary_entry,
             AppCommands::set_api_key,
-            AppCommands::read_api_key,
+            AppCommands::api_key_status,
+            AppCommands::test_api_key,
+            AppCommands::delete_api_key,
+            AppCommands::api_key_health,
+            AppCommands::list_models,
+            AppCommands::usage_stats,
+            AppCommands::list_history,
+            AppCommands::search_history,
+            AppCommands::delete_history,
+            AppCommands::clear_history,
+            AppCommands::export_history,
+            AppCommands::star_word,
+            AppCommands::list_vocabulary,
+            AppCommands::due_vocabulary,
+            AppCommands::review_vocabulary,
+            AppCommands::remove_vocabulary,
+            AppCommands::export_vocabulary_tsv,
+            AppCommands::export_vocabulary_apkg,
+            AppCommands::import_tmx,
+            AppCommands::export_tmx,
+            AppCommands::translation_memory_size,
+            AppCommands::clear_translation_memory,
+            AppCommands::cache_stats,
+            AppCommands::clear_cache,
+            AppCommands::set_cache_limits,
+            AppCommands::unlock_credentials,
+            AppCommands::credential_backend,
         ])
         .setup(|app| {
             let app_handle = app.handle();
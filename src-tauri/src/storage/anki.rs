@@ -0,0 +1,303 @@
+//! Writes `.apkg` decks: a zip holding an Anki 2.1 legacy collection database.
+
+use super::vocabulary::{VocabularyEntry, VocabularyError};
+use rusqlite::{params, Connection};
+use serde_json::json;
+use sha1::{Digest, Sha1};
+use std::io::Write;
+
+/// Fixed so that re-imported decks update the same note type.
+const MODEL_ID: i64 = 1_718_000_000_000;
+const MODEL_NAME: &str = "Silicon Translater Vocabulary";
+const TAG: &str = "silicon_translater";
+const FIELD_SEPARATOR: &str = "\u{1f}";
+
+const SCHEMA: &str = "
+CREATE TABLE col (
+    id integer primary key, crt integer not null, mod integer not null,
+    scm integer not null, ver integer not null, dty integer not null,
+    usn integer not null, ls integer not null, conf text not null,
+    models text not null, decks text not null, dconf text not null, tags text not null
+);
+CREATE TABLE notes (
+    id integer primary key, guid text not null, mid integer not null,
+    mod integer not null, usn integer not null, tags text not null,
+    flds text not null, sfld integer not null, csum integer not null,
+    flags integer not null, data text not null
+);
+CREATE TABLE cards (
+    id integer primary key, nid integer not null, did integer not null,
+    ord integer not null, mod integer not null, usn integer not null,
+    type integer not null, queue integer not null, due integer not null,
+    ivl integer not null, factor integer not null, reps integer not null,
+    lapses integer not null, left integer not null, odue integer not null,
+    odid integer not null, flags integer not null, data text not null
+);
+CREATE TABLE revlog (
+    id integer primary key, cid integer not null, usn integer not null,
+    ease integer not null, ivl integer not null, lastIvl integer not null,
+    factor integer not null, time integer not null, type integer not null
+);
+CREATE TABLE graves (usn integer not null, oid integer not null, type integer not null);
+CREATE INDEX ix_notes_usn on notes (usn);
+CREATE INDEX ix_cards_usn on cards (usn);
+CREATE INDEX ix_revlog_usn on revlog (usn);
+CREATE INDEX ix_cards_nid on cards (nid);
+CREATE INDEX ix_cards_sched on cards (did, queue, due);
+CREATE INDEX ix_revlog_cid on revlog (cid);
+CREATE INDEX ix_notes_csum on notes (csum);
+";
+
+/// Space-separated Anki tags marking the app and the language pair.
+pub(super) fn tags(entry: &VocabularyEntry) -> String {
+    let tag = |lang: &str| lang.split_whitespace().collect::<Vec<_>>().join("_");
+    format!(
+        "{TAG} {}::{}",
+        tag(&entry.source_lang),
+        tag(&entry.target_lang)
+    )
+}
+
+/// Builds the package in memory. Cards are exported as new; the app's own
+/// review schedule is not carried over.
+pub(super) fn build_package(
+    deck_name: &str,
+    entries: &[VocabularyEntry],
+    now: i64,
+) -> Result<Vec<u8>, VocabularyError> {
+    // Created exclusively and owner-only, and removed when dropped.
+    let file = tempfile::Builder::new()
+        .prefix("silicon_translater-")
+        .suffix(".anki2")
+        .tempfile()?;
+    write_collection(file.path(), deck_name, entries, now)?;
+    let collection = std::fs::read(file.path())?;
+
+    let mut package = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::FileOptions::default();
+    package.start_file("collection.anki2", options)?;
+    package.write_all(&collection)?;
+    package.start_file("media", options)?;
+    package.write_all(b"{}")?;
+    Ok(package.finish()?.into_inner())
+}
+
+fn write_collection(
+    path: &std::path::Path,
+    deck_name: &str,
+    entries: &[VocabularyEntry],
+    now: i64,
+) -> Result<(), VocabularyError> {
+    let seconds = now / 1000;
+    let deck_id = deck_id(deck_name);
+    let conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+    conn.execute(
+        "INSERT INTO col VALUES (1, ?1, ?2, ?2, 11, 0, 0, 0, ?3, ?4, ?5, ?6, '{}')",
+        params![
+            seconds,
+            now,
+            json!({
+                "activeDecks": [1], "addToCur": true, "collapseTime": 1200,
+                "curDeck": 1, "curModel": MODEL_ID.to_string(), "dueCounts": true,
+                "estTimes": true, "newBury": true, "newSpread": 0, "nextPos": 1,
+                "sortBackwards": false, "sortType": "noteFld", "timeLim": 0
+            })
+            .to_string(),
+            json!({ MODEL_ID.to_string(): model(deck_id, seconds) }).to_string(),
+            json!({
+                "1": deck(1, "Default", seconds),
+                deck_id.to_string(): deck(deck_id, deck_name, seconds),
+            })
+            .to_string(),
+            json!({ "1": deck_options() }).to_string(),
+        ],
+    )?;
+
+    for (index, entry) in entries.iter().enumerate() {
+        let id = now + index as i64;
+        let fields = [&entry.word, &entry.translation, &entry.context]
+            .map(|field| escape_html(field))
+            .join(FIELD_SEPARATOR);
+        let sort_field = escape_html(&entry.word);
+        conn.execute(
+            "INSERT INTO notes VALUES (?1, ?2, ?3, ?4, -1, ?5, ?6, ?7, ?8, 0, '')",
+            params![
+                id,
+                guid(entry),
+                MODEL_ID,
+                seconds,
+                format!(" {} ", tags(entry)),
+                fields,
+                sort_field,
+                checksum(&strip_html(&sort_field)),
+            ],
+        )?;
+        conn.execute(
+            "INSERT INTO cards VALUES (?1, ?1, ?2, 0, ?3, -1, 0, 0, ?4, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+            params![id, deck_id, seconds, index as i64 + 1],
+        )?;
+    }
+    Ok(())
+}
+
+fn model(deck_id: i64, seconds: i64) -> serde_json::Value {
+    let field = |name: &str, ord: u32| {
+        json!({
+            "name": name, "ord": ord, "font": "Arial", "media": [],
+            "rtl": false, "size": 20, "sticky": false
+        })
+    };
+    json!({
+        "id": MODEL_ID,
+        "name": MODEL_NAME,
+        "type": 0,
+        "mod": seconds,
+        "usn": -1,
+        "sortf": 0,
+        "did": deck_id,
+        "tags": [],
+        "vers": [],
+        "flds": [field("Word", 0), field("Translation", 1), field("Context", 2)],
+        "tmpls": [{
+            "name": "Card 1",
+            "ord": 0,
+            "qfmt": "{{Word}}",
+            "afmt": "{{FrontSide}}<hr id=answer>{{Translation}}<br><br><i>{{Context}}</i>",
+            "bqfmt": "",
+            "bafmt": "",
+            "did": null
+        }],
+        "req": [[0, "any", [0]]],
+        "css": ".card { font-family: arial; font-size: 20px; text-align: center; }",
+        "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+        "latexPost": "\\end{document}"
+    })
+}
+
+fn deck(id: i64, name: &str, seconds: i64) -> serde_json::Value {
+    json!({
+        "id": id, "name": name, "desc": "", "mod": seconds, "usn": -1,
+        "collapsed": false, "conf": 1, "dyn": 0, "extendNew": 10, "extendRev": 50,
+        "lrnToday": [0, 0], "newToday": [0, 0], "revToday": [0, 0], "timeToday": [0, 0]
+    })
+}
+
+fn deck_options() -> serde_json::Value {
+    json!({
+        "id": 1, "name": "Default", "mod": 0, "usn": 0, "autoplay": true,
+        "maxTaken": 60, "replayq": true, "timer": 0,
+        "new": {
+            "bury": true, "delays": [1, 10], "initialFactor": 2500,
+            "ints": [1, 4, 7], "order": 1, "perDay": 20, "separate": true
+        },
+        "rev": {
+            "bury": true, "ease4": 1.3, "fuzz": 0.05, "ivlFct": 1,
+            "maxIvl": 36500, "minSpace": 1, "perDay": 100
+        },
+        "lapse": {
+            "delays": [10], "leechAction": 0, "leechFails": 8, "minInt": 1, "mult": 0
+        }
+    })
+}
+
+fn sha1_hex(text: &str) -> String {
+    Sha1::digest(text.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Stable per word and language pair, so re-importing updates existing notes.
+fn guid(entry: &VocabularyEntry) -> String {
+    let key = [&entry.word, &entry.source_lang, &entry.target_lang].map(String::as_str);
+    sha1_hex(&key.join(FIELD_SEPARATOR))[..16].to_string()
+}
+
+/// Anki's duplicate check: the first 8 hex digits of the SHA-1 of the sort
+/// field with its HTML stripped.
+fn checksum(text: &str) -> i64 {
+    i64::from_str_radix(&sha1_hex(text)[..8], 16).unwrap_or_default()
+}
+
+/// Keeps deck ids stable per name and inside JavaScript's safe integer range.
+fn deck_id(name: &str) -> i64 {
+    1_000_000_000 + i64::from_str_radix(&sha1_hex(name)[..8], 16).unwrap_or_default()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\n', "<br>")
+}
+
+/// What Anki checksums: the field without tags and with the entities this
+/// module writes decoded.
+fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for ch in html.chars() {
+        match ch {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(ch),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn entry(word: &str) -> VocabularyEntry {
+        VocabularyEntry {
+            id: 1,
+            word: word.to_string(),
+            translation: "翻译".to_string(),
+            context: "line one\nline two".to_string(),
+            source_lang: "English".to_string(),
+            target_lang: "中文".to_string(),
+            created_at: 0,
+            ease: 2.5,
+            interval_days: 0,
+            repetitions: 0,
+            lapses: 0,
+            due_at: 0,
+        }
+    }
+
+    #[test]
+    fn strip_html_undoes_escaping() {
+        assert_eq!(strip_html(&escape_html("a < b & c")), "a < b & c");
+        assert_eq!(strip_html("one<br>two"), "onetwo");
+    }
+
+    #[test]
+    fn package_holds_a_collection_with_anki_checksums() {
+        let package = build_package("Words", &[entry("a<b>")], 1_700_000_000_000).unwrap();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(package)).unwrap();
+        let mut collection = Vec::new();
+        archive
+            .by_name("collection.anki2")
+            .unwrap()
+            .read_to_end(&mut collection)
+            .unwrap();
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), collection).unwrap();
+        let conn = Connection::open(file.path()).unwrap();
+        let (sort_field, csum): (String, i64) = conn
+            .query_row("SELECT sfld, csum FROM notes", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(sort_field, "a&lt;b&gt;");
+        assert_eq!(csum, checksum("a<b>"));
+    }
+}
@@ -0,0 +1,126 @@
+use super::usage::Period;
+use serde::{Deserialize, Serialize};
+
+/// Caps on daily and monthly spending; unset limits are not enforced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BudgetSettings {
+    pub daily_tokens: Option<u64>,
+    pub monthly_tokens: Option<u64>,
+    /// Estimated cost, so only models listed in `model_prices` count towards it.
+    pub daily_cost: Option<f64>,
+    pub monthly_cost: Option<f64>,
+    /// Fractions of a limit, e.g. `0.8`, at which a warning is emitted once per period.
+    pub warn_at: Vec<f64>,
+    pub on_exceeded: BudgetAction,
+    /// Model used instead of the configured one when `on_exceeded` is `downgrade`.
+    pub fallback_model: Option<String>,
+}
+
+impl Default for BudgetSettings {
+    fn default() -> Self {
+        Self {
+            daily_tokens: None,
+            monthly_tokens: None,
+            daily_cost: None,
+            monthly_cost: None,
+            warn_at: vec![0.8],
+            on_exceeded: BudgetAction::Refuse,
+            fallback_model: None,
+        }
+    }
+}
+
+impl BudgetSettings {
+    /// Every configured limit, as `(period, metric, limit)`.
+    pub fn limits(&self) -> Vec<(Period, BudgetMetric, f64)> {
+        [
+            (
+                Period::Daily,
+                BudgetMetric::Tokens,
+                self.daily_tokens.map(|limit| limit as f64),
+            ),
+            (
+                Period::Monthly,
+                BudgetMetric::Tokens,
+                self.monthly_tokens.map(|limit| limit as f64),
+            ),
+            (Period::Daily, BudgetMetric::Cost, self.daily_cost),
+            (Period::Monthly, BudgetMetric::Cost, self.monthly_cost),
+        ]
+        .into_iter()
+        .filter_map(|(period, metric, limit)| Some((period, metric, limit?)))
+        .collect()
+    }
+
+    pub fn fallback_model(&self) -> Option<&str> {
+        self.fallback_model
+            .as_deref()
+            .map(str::trim)
+            .filter(|model| !model.is_empty())
+    }
+}
+
+/// What happens to requests once a limit is reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetAction {
+    /// Hard cap: requests fail until the period rolls over.
+    Refuse,
+    /// Keep translating with `fallback_model`, which still spends tokens.
+    Downgrade,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetMetric {
+    Tokens,
+    Cost,
+}
+
+/// How much of one limit has been used in the current period.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BudgetUsage {
+    pub period: Period,
+    pub metric: BudgetMetric,
+    pub used: f64,
+    pub limit: f64,
+}
+
+impl BudgetUsage {
+    pub fn fraction(&self) -> f64 {
+        if self.limit <= 0.0 {
+            return 1.0;
+        }
+        self.used / self.limit
+    }
+}
+
+impl std::fmt::Display for BudgetUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let period = match self.period {
+            Period::Daily => "daily",
+            Period::Monthly => "monthly",
+        };
+        match self.metric {
+            BudgetMetric::Tokens => {
+                write!(f, "{period} token budget: {} of {}", self.used, self.limit)
+            }
+            BudgetMetric::Cost => write!(
+                f,
+                "{period} cost budget: {:.4} of {:.4}",
+                self.used, self.limit
+            ),
+        }
+    }
+}
+
+/// Sent when usage crosses a `warn_at` threshold or a request is downgraded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetWarning {
+    pub request_id: Option<String>,
+    pub usage: BudgetUsage,
+    pub threshold: f64,
+    /// Set when the request was sent to this model instead of the configured one.
+    pub fallback_model: Option<String>,
+}
@@ -0,0 +1,203 @@
+use super::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::SystemTime;
+use tracing::warn;
+
+const ENTRY_EXTENSION: &str = "json";
+
+#[derive(Debug, thiserror::Error)]
+pub enum CacheError {
+    #[error("cache io error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Limits of the translation cache.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheSettings {
+    pub enabled: bool,
+    /// Entries kept in memory; the least recently used one is evicted first.
+    pub memory_entries: usize,
+    /// Size of the on-disk store; `0` keeps translations in memory only.
+    pub max_disk_bytes: u64,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            memory_entries: 256,
+            max_disk_bytes: 20 * 1024 * 1024,
+        }
+    }
+}
+
+/// A translation stored under its cache key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedTranslation {
+    pub translated_text: String,
+    pub model: String,
+}
+
+/// One JSON file per cache key; file modification times order evictions.
+#[derive(Clone)]
+pub struct CacheStore {
+    dir: PathBuf,
+}
+
+impl CacheStore {
+    pub fn open(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn get(&self, key: &str) -> Option<CachedTranslation> {
+        let path = self.path(key);
+        let bytes = std::fs::read(&path).ok()?;
+        match serde_json::from_slice(&bytes) {
+            Ok(entry) => {
+                // Reading counts as use, so popular entries survive `prune`.
+                if let Ok(file) = std::fs::File::options().write(true).open(&path) {
+                    let _ = file.set_modified(SystemTime::now());
+                }
+                Some(entry)
+            }
+            Err(err) => {
+                warn!(%err, path = %path.display(), "removing unreadable cache entry");
+                let _ = std::fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    pub fn put(&self, key: &str, entry: &CachedTranslation) -> std::io::Result<()> {
+        let bytes = serde_json::to_vec(entry)?;
+        write_atomic(&self.path(key), &bytes)
+    }
+
+    /// Number of entries and their total size in bytes.
+    pub fn size(&self) -> std::io::Result<(usize, u64)> {
+        let entries = self.entries()?;
+        Ok((
+            entries.len(),
+            entries.iter().map(|(_, bytes, _)| bytes).sum(),
+        ))
+    }
+
+    /// Removes every entry and returns how many there were.
+    pub fn clear(&self) -> std::io::Result<usize> {
+        let entries = self.entries()?;
+        for (path, _, _) in &entries {
+            std::fs::remove_file(path)?;
+        }
+        Ok(entries.len())
+    }
+
+    /// Removes the least recently used entries until at most `max_bytes` remain.
+    pub fn prune(&self, max_bytes: u64) -> std::io::Result<usize> {
+        let mut entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|(_, bytes, _)| bytes).sum();
+        entries.sort_by_key(|(_, _, modified)| *modified);
+
+        let mut removed = 0;
+        for (path, bytes, _) in entries {
+            if total <= max_bytes {
+                break;
+            }
+            std::fs::remove_file(&path)?;
+            total -= bytes;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.{ENTRY_EXTENSION}"))
+    }
+
+    fn entries(&self) -> std::io::Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let read_dir = match std::fs::read_dir(&self.dir) {
+            Ok(read_dir) => read_dir,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut entries = Vec::new();
+        for entry in read_dir {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(ENTRY_EXTENSION) {
+                continue;
+            }
+            let metadata = std::fs::metadata(&path)?;
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            entries.push((path, metadata.len(), modified));
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn entry(text: &str) -> CachedTranslation {
+        CachedTranslation {
+            translated_text: text.to_string(),
+            model: "model".to_string(),
+        }
+    }
+
+    fn touch(store: &CacheStore, key: &str, age_secs: u64) {
+        let file = std::fs::File::options()
+            .write(true)
+            .open(store.path(key))
+            .unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(age_secs))
+            .unwrap();
+    }
+
+    #[test]
+    fn prune_removes_the_least_recently_used_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CacheStore::open(dir.path().to_path_buf());
+        for (key, age) in [("old", 300), ("middle", 200), ("new", 100)] {
+            store.put(key, &entry(key)).unwrap();
+            touch(&store, key, age);
+        }
+        let (count, bytes) = store.size().unwrap();
+        assert_eq!(count, 3);
+
+        assert_eq!(store.prune(bytes).unwrap(), 0);
+        let new_bytes = std::fs::metadata(store.path("new")).unwrap().len();
+        assert_eq!(store.prune(new_bytes).unwrap(), 2);
+        assert!(store.get("old").is_none());
+        assert!(store.get("middle").is_none());
+        assert_eq!(store.get("new"), Some(entry("new")));
+    }
+
+    #[test]
+    fn reading_an_entry_protects_it_from_pruning() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CacheStore::open(dir.path().to_path_buf());
+        for (key, age) in [("read", 300), ("unread", 100)] {
+            store.put(key, &entry(key)).unwrap();
+            touch(&store, key, age);
+        }
+        assert!(store.get("read").is_some());
+
+        let read_bytes = std::fs::metadata(store.path("read")).unwrap().len();
+        assert_eq!(store.prune(read_bytes).unwrap(), 1);
+        assert!(store.get("read").is_some());
+        assert!(store.get("unread").is_none());
+    }
+
+    #[test]
+    fn unreadable_entries_are_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CacheStore::open(dir.path().to_path_buf());
+        std::fs::write(store.path("broken"), b"not json").unwrap();
+
+        assert!(store.get("broken").is_none());
+        assert_eq!(store.size().unwrap(), (0, 0));
+    }
+}
@@ -0,0 +1,625 @@
+use super::{app_config_dir, write_atomic_private};
+use crate::api_client::ApiKey;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+/// Name given to a key stored without one, including keys saved before names existed.
+pub const DEFAULT_KEY_NAME: &str = "default";
+
+/// Environment variable that unlocks the encrypted-file backend without a prompt.
+pub const PASSPHRASE_ENV: &str = "SILICON_TRANSLATER_PASSPHRASE";
+
+#[derive(Debug, thiserror::Error)]
+pub enum CredentialError {
+    #[error("no credential backend available ({0})")]
+    NoBackend(String),
+    #[error("credential store is locked, unlock it with the passphrase first")]
+    Locked,
+    #[error("wrong passphrase or corrupted credential file")]
+    Decrypt,
+    #[error("invalid credential file: {0}")]
+    InvalidFile(&'static str),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("secret service error: {0}")]
+    SecretService(String),
+    #[error("windows error: {0}")]
+    Windows(String),
+}
+
+impl CredentialError {
+    /// Stable identifier reported to the frontend.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NoBackend(_) => "credentials_unavailable",
+            Self::Locked => "credentials_locked",
+            Self::Decrypt => "credentials_wrong_passphrase",
+            Self::InvalidFile(_) => "credentials_invalid_file",
+            Self::Io(_) => "credentials_storage",
+            Self::SecretService(_) => "secret_service",
+            Self::Windows(_) => "windows_credentials",
+        }
+    }
+}
+
+/// A place the API key secret can be kept, such as the OS keyring or an encrypted file.
+///
+/// Backends hold a single opaque string; `CredentialStore` keeps its key list in it.
+pub trait CredentialBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn read(&self) -> Result<Option<String>, CredentialError>;
+    fn write(&self, api_key: &str) -> Result<(), CredentialError>;
+    /// Removes the stored key; deleting a missing key is not an error.
+    fn delete(&self) -> Result<(), CredentialError>;
+
+    /// Supplies the passphrase for backends that need one; keyring backends ignore it.
+    fn unlock(&self, _passphrase: &str) -> Result<(), CredentialError> {
+        Ok(())
+    }
+
+    /// Whether the keys sit in a file of ours rather than an OS keyring.
+    fn is_file(&self) -> bool {
+        false
+    }
+
+    /// Whether a passphrase is needed before the keys can be read.
+    fn is_locked(&self) -> bool {
+        false
+    }
+}
+
+/// Where the keys are kept, so the UI can say when they ended up in a file.
+#[derive(Debug, Clone, Serialize)]
+pub struct CredentialBackendStatus {
+    /// `None` when no backend is usable.
+    pub backend: Option<&'static str>,
+    /// Set when the OS keyring was unavailable and the encrypted file is used instead.
+    pub file_fallback: bool,
+    pub locked: bool,
+    /// Why each backend tried before the chosen one was rejected.
+    pub rejected: Vec<String>,
+}
+
+/// What the UI may know about the stored keys without seeing them.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKeyStatus {
+    pub present: bool,
+    pub keys: Vec<MaskedApiKey>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MaskedApiKey {
+    pub name: String,
+    pub masked: String,
+}
+
+const VISIBLE_SUFFIX: usize = 4;
+
+/// Replaces all but the last few characters; short keys are hidden entirely.
+fn mask_api_key(api_key: &str) -> String {
+    let chars: Vec<char> = api_key.trim().chars().collect();
+    if chars.len() <= VISIBLE_SUFFIX * 2 {
+        return "••••".to_string();
+    }
+    let suffix: String = chars[chars.len() - VISIBLE_SUFFIX..].iter().collect();
+    format!("••••{suffix}")
+}
+
+/// Stores the API keys in the first usable backend, chosen at startup.
+#[derive(Clone)]
+pub struct CredentialStore {
+    backend: Option<Arc<dyn CredentialBackend>>,
+    /// Why each candidate backend was rejected, reported when none is usable.
+    rejected: Arc<Vec<String>>,
+    /// The backend's content as last read or written, so translations do not
+    /// go to the keyring or decrypt the file each time; `None` until read.
+    stored: Arc<Mutex<Option<Option<Zeroizing<String>>>>>,
+}
+
+impl CredentialStore {
+    /// Tries the platform keyring first and falls back to the encrypted file.
+    pub fn new() -> Self {
+        let mut rejected = Vec::new();
+        let backend = select_backend(&mut rejected);
+        match &backend {
+            Some(backend) => info!(backend = backend.name(), "selected credential backend"),
+            None => warn!(?rejected, "no credential backend available"),
+        }
+        Self {
+            backend,
+            rejected: Arc::new(rejected),
+            stored: Arc::default(),
+        }
+    }
+
+    pub fn backend_status(&self) -> CredentialBackendStatus {
+        let backend = self.backend.as_deref();
+        CredentialBackendStatus {
+            backend: backend.map(|backend| backend.name()),
+            file_fallback: backend.is_some_and(|backend| backend.is_file()),
+            locked: backend.is_some_and(|backend| backend.is_locked()),
+            rejected: self.rejected.to_vec(),
+        }
+    }
+
+    /// Stored keys in rotation order.
+    pub fn api_keys(&self) -> Result<Vec<ApiKey>, CredentialError> {
+        let mut cached = self.stored.lock().expect("credential cache lock");
+        let stored = match &*cached {
+            Some(stored) => stored,
+            None => cached.insert(self.backend()?.read()?.map(Zeroizing::new)),
+        };
+        let Some(stored) = stored else {
+            return Ok(Vec::new());
+        };
+        // A bare string is a key saved before named keys were supported.
+        Ok(serde_json::from_str(stored).unwrap_or_else(|_| {
+            vec![ApiKey {
+                name: DEFAULT_KEY_NAME.to_string(),
+                secret: stored.trim().to_string(),
+            }]
+        }))
+    }
+
+    /// Adds a key, or replaces the secret of the key with the same name.
+    pub fn set_api_key(&self, name: &str, secret: &str) -> Result<(), CredentialError> {
+        let mut keys = self.api_keys()?;
+        let key = ApiKey {
+            name: name.to_string(),
+            secret: secret.trim().to_string(),
+        };
+        match keys.iter_mut().find(|existing| existing.name == name) {
+            Some(existing) => *existing = key,
+            None => keys.push(key),
+        }
+        self.save(&keys)
+    }
+
+    /// Removes the named key, or every key when `name` is `None`.
+    pub fn delete_api_key(&self, name: Option<&str>) -> Result<(), CredentialError> {
+        if let Some(name) = name {
+            let mut keys = self.api_keys()?;
+            keys.retain(|key| key.name != name);
+            if !keys.is_empty() {
+                return self.save(&keys);
+            }
+        }
+        self.backend()?.delete()?;
+        *self.stored.lock().expect("credential cache lock") = Some(None);
+        Ok(())
+    }
+
+    /// Whether keys are stored, with only their last characters visible.
+    pub fn api_key_status(&self) -> Result<ApiKeyStatus, CredentialError> {
+        let keys = self.api_keys()?;
+        Ok(ApiKeyStatus {
+            present: !keys.is_empty(),
+            keys: keys
+                .iter()
+                .map(|key| MaskedApiKey {
+                    name: key.name.clone(),
+                    masked: mask_api_key(&key.secret),
+                })
+                .collect(),
+        })
+    }
+
+    pub fn unlock(&self, passphrase: &str) -> Result<(), CredentialError> {
+        self.backend()?.unlock(passphrase)?;
+        *self.stored.lock().expect("credential cache lock") = None;
+        Ok(())
+    }
+
+    fn save(&self, keys: &[ApiKey]) -> Result<(), CredentialError> {
+        let stored = Zeroizing::new(serde_json::to_string(keys).expect("api keys serialize"));
+        self.backend()?.write(&stored)?;
+        *self.stored.lock().expect("credential cache lock") = Some(Some(stored));
+        Ok(())
+    }
+
+    fn backend(&self) -> Result<&dyn CredentialBackend, CredentialError> {
+        self.backend
+            .as_deref()
+            .ok_or_else(|| CredentialError::NoBackend(self.rejected.join("; ")))
+    }
+}
+
+type Opener = fn() -> Result<Arc<dyn CredentialBackend>, CredentialError>;
+
+fn select_backend(rejected: &mut Vec<String>) -> Option<Arc<dyn CredentialBackend>> {
+    let candidates: &[(&str, Opener)] = &[
+        #[cfg(target_os = "windows")]
+        ("windows credential manager", || {
+            Ok(Arc::new(WindowsBackend) as _)
+        }),
+        #[cfg(target_os = "linux")]
+        ("secret service", || {
+            Ok(Arc::new(SecretServiceBackend::probe()?) as _)
+        }),
+        ("encrypted file", || {
+            let path = app_config_dir().join(CREDENTIALS_FILE);
+            Ok(Arc::new(EncryptedFileBackend::open(path)?) as _)
+        }),
+    ];
+
+    for (name, open) in candidates {
+        match open() {
+            Ok(backend) => return Some(backend),
+            Err(err) => rejected.push(format!("{name}: {err}")),
+        }
+    }
+    None
+}
+
+/// Windows Credential Manager.
+#[cfg(target_os = "windows")]
+struct WindowsBackend;
+
+#[cfg(target_os = "windows")]
+impl CredentialBackend for WindowsBackend {
+    fn name(&self) -> &'static str {
+        "windows-credential-manager"
+    }
+
+    fn read(&self) -> Result<Option<String>, CredentialError> {
+        super::windows_impl::read_credential()
+    }
+
+    fn write(&self, api_key: &str) -> Result<(), CredentialError> {
+        super::windows_impl::write_credential(api_key)
+    }
+
+    fn delete(&self) -> Result<(), CredentialError> {
+        if self.read()?.is_none() {
+            return Ok(());
+        }
+        super::windows_impl::delete_credential()
+    }
+}
+
+/// The freedesktop Secret Service (GNOME Keyring, KWallet).
+#[cfg(target_os = "linux")]
+struct SecretServiceBackend;
+
+#[cfg(target_os = "linux")]
+mod secret_service_impl {
+    use super::{CredentialBackend, CredentialError, SecretServiceBackend};
+    use secret_service::blocking::{Item, SecretService};
+    use secret_service::EncryptionType;
+    use std::collections::HashMap;
+
+    const LABEL: &str = "silicon_translater API key";
+
+    fn attributes() -> HashMap<&'static str, &'static str> {
+        HashMap::from([
+            ("application", "silicon_translater"),
+            ("account", "api_key"),
+        ])
+    }
+
+    fn connect() -> Result<SecretService<'static>, CredentialError> {
+        SecretService::connect(EncryptionType::Dh).map_err(into_error)
+    }
+
+    fn into_error(err: secret_service::Error) -> CredentialError {
+        CredentialError::SecretService(err.to_string())
+    }
+
+    fn find<'a>(service: &'a SecretService<'_>) -> Result<Option<Item<'a>>, CredentialError> {
+        let result = service.search_items(attributes()).map_err(into_error)?;
+        if let Some(item) = result.unlocked.into_iter().next() {
+            return Ok(Some(item));
+        }
+        match result.locked.into_iter().next() {
+            Some(item) => {
+                item.unlock().map_err(into_error)?;
+                Ok(Some(item))
+            }
+            None => Ok(None),
+        }
+    }
+
+    impl SecretServiceBackend {
+        /// Fails when no Secret Service daemon answers on the session bus.
+        pub(super) fn probe() -> Result<Self, CredentialError> {
+            connect()?;
+            Ok(Self)
+        }
+    }
+
+    impl CredentialBackend for SecretServiceBackend {
+        fn name(&self) -> &'static str {
+            "secret-service"
+        }
+
+        fn read(&self) -> Result<Option<String>, CredentialError> {
+            let service = connect()?;
+            let Some(item) = find(&service)? else {
+                return Ok(None);
+            };
+            let secret = item.get_secret().map_err(into_error)?;
+            String::from_utf8(secret)
+                .map(Some)
+                .map_err(|_| CredentialError::SecretService("stored key is not UTF-8".into()))
+        }
+
+        fn write(&self, api_key: &str) -> Result<(), CredentialError> {
+            let service = connect()?;
+            let collection = service.get_default_collection().map_err(into_error)?;
+            if collection.is_locked().map_err(into_error)? {
+                collection.unlock().map_err(into_error)?;
+            }
+            collection
+                .create_item(LABEL, attributes(), api_key.as_bytes(), true, "text/plain")
+                .map_err(into_error)?;
+            Ok(())
+        }
+
+        fn delete(&self) -> Result<(), CredentialError> {
+            let service = connect()?;
+            if let Some(item) = find(&service)? {
+                item.delete().map_err(into_error)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+const CREDENTIALS_FILE: &str = "credentials.enc";
+const FILE_MAGIC: &[u8; 4] = b"STC1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+
+/// Portable fallback: the key encrypted with ChaCha20-Poly1305 under an Argon2id-derived key.
+///
+/// File layout is `magic | salt | nonce | ciphertext`. The key is derived once
+/// at unlock and kept with its salt, so writes reuse the salt and draw only a
+/// fresh nonce; the passphrase itself is not kept.
+struct EncryptedFileBackend {
+    path: PathBuf,
+    key: Mutex<Option<DerivedKey>>,
+}
+
+struct DerivedKey {
+    salt: [u8; SALT_LEN],
+    key: Zeroizing<[u8; KEY_LEN]>,
+}
+
+impl EncryptedFileBackend {
+    fn open(path: PathBuf) -> Result<Self, CredentialError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let backend = Self {
+            path,
+            key: Mutex::new(None),
+        };
+        if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+            let passphrase = Zeroizing::new(passphrase);
+            if let Err(err) = backend.unlock(&passphrase) {
+                warn!(%err, "{PASSPHRASE_ENV} does not unlock the credential file");
+            }
+        }
+        Ok(backend)
+    }
+
+    fn read_file(&self) -> Result<Option<Vec<u8>>, CredentialError> {
+        match std::fs::read(&self.path) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl CredentialBackend for EncryptedFileBackend {
+    fn name(&self) -> &'static str {
+        "encrypted-file"
+    }
+
+    fn read(&self) -> Result<Option<String>, CredentialError> {
+        let Some(bytes) = self.read_file()? else {
+            return Ok(None);
+        };
+        let mut key = self.key.lock().expect("credential key lock");
+        let derived = key.as_ref().ok_or(CredentialError::Locked)?;
+        let sealed = Sealed::parse(&bytes)?;
+        if sealed.salt != derived.salt {
+            // Rewritten under another salt since the unlock; only the
+            // passphrase can derive the new key.
+            *key = None;
+            return Err(CredentialError::Locked);
+        }
+        sealed.open(&derived.key).map(Some)
+    }
+
+    fn write(&self, api_key: &str) -> Result<(), CredentialError> {
+        let key = self.key.lock().expect("credential key lock");
+        let derived = key.as_ref().ok_or(CredentialError::Locked)?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(derived.key.as_ref()))
+            .encrypt(&nonce, api_key.as_bytes())
+            .map_err(|_| CredentialError::InvalidFile("encryption failed"))?;
+
+        let mut bytes =
+            Vec::with_capacity(FILE_MAGIC.len() + SALT_LEN + NONCE_LEN + ciphertext.len());
+        bytes.extend_from_slice(FILE_MAGIC);
+        bytes.extend_from_slice(&derived.salt);
+        bytes.extend_from_slice(&nonce);
+        bytes.extend_from_slice(&ciphertext);
+        write_atomic_private(&self.path, &bytes)?;
+        Ok(())
+    }
+
+    /// Needs no passphrase: the file is useless without it anyway.
+    fn delete(&self) -> Result<(), CredentialError> {
+        match std::fs::remove_file(&self.path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn is_file(&self) -> bool {
+        true
+    }
+
+    fn is_locked(&self) -> bool {
+        self.key.lock().expect("credential key lock").is_none()
+    }
+
+    /// Derives the key, checking it against an existing file before keeping it.
+    fn unlock(&self, passphrase: &str) -> Result<(), CredentialError> {
+        let derived = match self.read_file()? {
+            Some(bytes) => {
+                let sealed = Sealed::parse(&bytes)?;
+                let key = derive_key(passphrase, sealed.salt)?;
+                sealed.open(&key)?;
+                DerivedKey {
+                    salt: sealed.salt.try_into().expect("salt length"),
+                    key,
+                }
+            }
+            None => {
+                let mut salt = [0u8; SALT_LEN];
+                chacha20poly1305::aead::rand_core::RngCore::fill_bytes(&mut OsRng, &mut salt);
+                let key = derive_key(passphrase, &salt)?;
+                DerivedKey { salt, key }
+            }
+        };
+        *self.key.lock().expect("credential key lock") = Some(derived);
+        Ok(())
+    }
+}
+
+/// The parts of a credential file.
+struct Sealed<'a> {
+    salt: &'a [u8],
+    nonce: &'a [u8],
+    ciphertext: &'a [u8],
+}
+
+impl<'a> Sealed<'a> {
+    fn parse(bytes: &'a [u8]) -> Result<Self, CredentialError> {
+        let body = bytes
+            .strip_prefix(FILE_MAGIC)
+            .ok_or(CredentialError::InvalidFile("unknown format"))?;
+        if body.len() < SALT_LEN + NONCE_LEN {
+            return Err(CredentialError::InvalidFile("truncated"));
+        }
+        let (salt, rest) = body.split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        Ok(Self {
+            salt,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    fn open(&self, key: &[u8; KEY_LEN]) -> Result<String, CredentialError> {
+        let plaintext = ChaCha20Poly1305::new(Key::from_slice(key))
+            .decrypt(Nonce::from_slice(self.nonce), self.ciphertext)
+            .map_err(|_| CredentialError::Decrypt)?;
+        String::from_utf8(plaintext).map_err(|_| CredentialError::Decrypt)
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Zeroizing<[u8; KEY_LEN]>, CredentialError> {
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+        .map_err(|_| CredentialError::InvalidFile("key derivation failed"))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend(dir: &tempfile::TempDir) -> EncryptedFileBackend {
+        EncryptedFileBackend::open(dir.path().join(CREDENTIALS_FILE)).expect("open")
+    }
+
+    fn sealed(passphrase: &str) -> (tempfile::TempDir, EncryptedFileBackend) {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let writer = backend(&dir);
+        writer.unlock(passphrase).expect("unlock");
+        writer.write("sk-secret").expect("write");
+        (dir, writer)
+    }
+
+    #[test]
+    fn encrypted_file_round_trips_across_instances() {
+        let (dir, writer) = sealed("correct horse");
+        assert_eq!(writer.read().unwrap().as_deref(), Some("sk-secret"));
+
+        let reader = backend(&dir);
+        assert!(reader.is_locked());
+        assert!(matches!(reader.read(), Err(CredentialError::Locked)));
+        reader.unlock("correct horse").expect("unlock");
+        assert_eq!(reader.read().unwrap().as_deref(), Some("sk-secret"));
+        let on_disk = std::fs::read(dir.path().join(CREDENTIALS_FILE)).unwrap();
+        assert!(!on_disk.windows(9).any(|window| window == b"sk-secret"));
+    }
+
+    #[test]
+    fn a_wrong_passphrase_is_rejected() {
+        let (dir, _) = sealed("correct horse");
+        let reader = backend(&dir);
+        assert!(matches!(
+            reader.unlock("battery staple"),
+            Err(CredentialError::Decrypt)
+        ));
+        assert!(reader.is_locked());
+    }
+
+    #[test]
+    fn damaged_files_are_rejected() {
+        let (dir, _) = sealed("correct horse");
+        let path = dir.path().join(CREDENTIALS_FILE);
+        let bytes = std::fs::read(&path).unwrap();
+
+        std::fs::write(&path, &bytes[..FILE_MAGIC.len() + SALT_LEN]).unwrap();
+        assert!(matches!(
+            backend(&dir).unlock("correct horse"),
+            Err(CredentialError::InvalidFile("truncated"))
+        ));
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, &corrupted).unwrap();
+        assert!(matches!(
+            backend(&dir).unlock("correct horse"),
+            Err(CredentialError::Decrypt)
+        ));
+
+        std::fs::write(&path, b"not a credential file").unwrap();
+        assert!(matches!(
+            backend(&dir).unlock("correct horse"),
+            Err(CredentialError::InvalidFile("unknown format"))
+        ));
+    }
+
+    #[test]
+    fn a_file_rewritten_under_another_salt_locks_again() {
+        let (dir, writer) = sealed("correct horse");
+        let other = tempfile::tempdir().expect("temp dir");
+        let rewriter = backend(&other);
+        rewriter.unlock("correct horse").expect("unlock");
+        rewriter.write("sk-other").expect("write");
+        std::fs::copy(
+            other.path().join(CREDENTIALS_FILE),
+            dir.path().join(CREDENTIALS_FILE),
+        )
+        .unwrap();
+
+        assert!(matches!(writer.read(), Err(CredentialError::Locked)));
+        writer.unlock("correct horse").expect("unlock");
+        assert_eq!(writer.read().unwrap().as_deref(), Some("sk-other"));
+    }
+}
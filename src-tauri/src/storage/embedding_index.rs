@@ -0,0 +1,294 @@
+use super::{HistoryError, MemoryMatch};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Longer texts are neither embedded nor offered as examples.
+pub const MAX_EMBEDDED_CHARS: usize = 2_000;
+/// Only the newest history entries are searched for examples or scanned for
+/// texts still to embed, so a lookup stays fast however long the history gets.
+const MAX_SCANNED_ENTRIES: usize = 2_000;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS text_embeddings (
+    text_hash TEXT NOT NULL,
+    model TEXT NOT NULL,
+    vector BLOB NOT NULL,
+    PRIMARY KEY (text_hash, model)
+);
+";
+
+/// Few-shot examples picked by embedding similarity instead of edit distance.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SemanticRetrievalSettings {
+    /// Off by default: every translation then also makes an `/embeddings` call.
+    pub enabled: bool,
+    /// Embedding model offered by the active provider.
+    pub model: String,
+    pub top_k: usize,
+    /// Minimum cosine similarity of an example, from 0 to 1.
+    pub min_score: f32,
+}
+
+impl Default for SemanticRetrievalSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            model: "BAAI/bge-m3".to_string(),
+            top_k: 3,
+            min_score: 0.75,
+        }
+    }
+}
+
+/// Embeddings of source texts keyed by a hash of the text, stored in the
+/// history database so each text is embedded once per model.
+#[derive(Clone)]
+pub struct EmbeddingIndex {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl EmbeddingIndex {
+    pub(super) fn new(conn: Arc<Mutex<Connection>>) -> Self {
+        if let Err(err) = conn.lock().expect("history lock").execute_batch(SCHEMA) {
+            warn!(%err, "failed to create the embedding index");
+        }
+        Self { conn }
+    }
+
+    /// The stored vector of `text`, if it was embedded with `model` before.
+    pub fn vector(&self, model: &str, text: &str) -> Result<Option<Vec<f32>>, HistoryError> {
+        let conn = self.conn.lock().expect("history lock");
+        Ok(stored_vector(&conn, model, &text_hash(text))?)
+    }
+
+    /// Distinct source texts among the newest history entries that have no
+    /// vector for `model`, newest first.
+    pub fn pending(&self, model: &str, limit: usize) -> Result<Vec<String>, HistoryError> {
+        let conn = self.conn.lock().expect("history lock");
+        let mut statement = conn.prepare(
+            "SELECT source_text FROM history WHERE length(source_text) <= ?1 \
+             ORDER BY id DESC LIMIT ?2",
+        )?;
+        let rows = statement
+            .query_map(params![MAX_EMBEDDED_CHARS, MAX_SCANNED_ENTRIES], |row| {
+                row.get::<_, String>(0)
+            })?;
+        let mut exists = conn
+            .prepare_cached("SELECT 1 FROM text_embeddings WHERE text_hash = ?1 AND model = ?2")?;
+        let mut seen = HashSet::new();
+        let mut texts = Vec::new();
+        for row in rows {
+            let text = row?;
+            if texts.len() >= limit {
+                break;
+            }
+            if !seen.insert(text.clone()) {
+                continue;
+            }
+            if !exists.exists(params![text_hash(&text), model])? {
+                texts.push(text);
+            }
+        }
+        Ok(texts)
+    }
+
+    /// Stores each vector under the hash of its text, whether or not the text
+    /// is in the history yet.
+    pub fn store(&self, model: &str, vectors: &[(String, Vec<f32>)]) -> Result<(), HistoryError> {
+        let mut conn = self.conn.lock().expect("history lock");
+        let transaction = conn.transaction()?;
+        for (text, vector) in vectors {
+            transaction.execute(
+                "INSERT OR REPLACE INTO text_embeddings (text_hash, model, vector) \
+                 VALUES (?1, ?2, ?3)",
+                params![text_hash(text), model, encode(vector)],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Past translations in the same direction whose source is closest to
+    /// `vector`, best first. Only the newest entries of that direction are
+    /// compared.
+    pub fn nearest(
+        &self,
+        vector: &[f32],
+        source_lang: &str,
+        target_lang: &str,
+        settings: &SemanticRetrievalSettings,
+    ) -> Result<Vec<MemoryMatch>, HistoryError> {
+        let query = normalized(vector);
+        let conn = self.conn.lock().expect("history lock");
+        let mut statement = conn.prepare(
+            "SELECT source_text, translated_text FROM history \
+             WHERE source_lang = ?1 AND target_lang = ?2 \
+             ORDER BY id DESC LIMIT ?3",
+        )?;
+        let rows = statement.query_map(
+            params![source_lang, target_lang, MAX_SCANNED_ENTRIES],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )?;
+
+        // Repeated texts keep their newest translation.
+        let mut seen = HashSet::new();
+        let mut matches = Vec::new();
+        for row in rows {
+            let (source_text, target_text) = row?;
+            if !seen.insert(source_text.clone()) {
+                continue;
+            }
+            let Some(candidate) = stored_vector(&conn, &settings.model, &text_hash(&source_text))?
+            else {
+                continue;
+            };
+            if candidate.len() != query.len() {
+                continue;
+            }
+            let similarity: f32 = query.iter().zip(&candidate).map(|(a, b)| a * b).sum();
+            if similarity >= settings.min_score {
+                matches.push(MemoryMatch {
+                    source_text,
+                    target_text,
+                    similarity,
+                });
+            }
+        }
+        matches.sort_by(|left, right| right.similarity.total_cmp(&left.similarity));
+        matches.truncate(settings.top_k);
+        Ok(matches)
+    }
+}
+
+fn stored_vector(conn: &Connection, model: &str, hash: &str) -> rusqlite::Result<Option<Vec<f32>>> {
+    let vector: Option<Vec<u8>> = conn
+        .prepare_cached("SELECT vector FROM text_embeddings WHERE text_hash = ?1 AND model = ?2")?
+        .query_row(params![hash, model], |row| row.get(0))
+        .optional()?;
+    Ok(vector.map(|bytes| decode(&bytes)))
+}
+
+fn text_hash(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Unit length, so cosine similarity becomes a dot product.
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let norm = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        return vector.to_vec();
+    }
+    vector.iter().map(|value| value / norm).collect()
+}
+
+/// Normalized little-endian `f32`s.
+fn encode(vector: &[f32]) -> Vec<u8> {
+    normalized(vector)
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+fn decode(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{HistoryEntry, HistoryStore};
+    use std::path::Path;
+
+    const MODEL: &str = "test-model";
+
+    fn history() -> HistoryStore {
+        HistoryStore::open(Path::new(":memory:"))
+    }
+
+    fn record(history: &HistoryStore, source: &str, target: &str, source_lang: &str) {
+        let entry = HistoryEntry {
+            id: 0,
+            source_text: source.to_string(),
+            translated_text: target.to_string(),
+            source_lang: source_lang.to_string(),
+            target_lang: "zh".to_string(),
+            model: MODEL.to_string(),
+            selection_source: None,
+            created_at: 0,
+        };
+        history.record(&entry).expect("record");
+    }
+
+    fn settings() -> SemanticRetrievalSettings {
+        SemanticRetrievalSettings {
+            enabled: true,
+            model: MODEL.to_string(),
+            top_k: 3,
+            min_score: 0.5,
+        }
+    }
+
+    #[test]
+    fn vectors_are_kept_without_a_history_entry() {
+        let history = history();
+        let index = history.embedding_index();
+        index
+            .store(MODEL, &[("hello".to_string(), vec![3.0, 4.0])])
+            .expect("store");
+        assert_eq!(index.vector(MODEL, "hello").unwrap(), Some(vec![0.6, 0.8]));
+        assert_eq!(index.vector("other-model", "hello").unwrap(), None);
+        assert_eq!(index.vector(MODEL, "hello!").unwrap(), None);
+    }
+
+    #[test]
+    fn pending_lists_each_unembedded_text_once_newest_first() {
+        let history = history();
+        let index = history.embedding_index();
+        record(&history, "one", "一", "en");
+        record(&history, "two", "二", "en");
+        record(&history, "one", "一", "en");
+        record(&history, "three", "三", "en");
+        index
+            .store(MODEL, &[("three".to_string(), vec![1.0])])
+            .expect("store");
+        assert_eq!(index.pending(MODEL, 10).unwrap(), ["one", "two"]);
+        assert_eq!(index.pending(MODEL, 1).unwrap(), ["one"]);
+    }
+
+    #[test]
+    fn nearest_keeps_the_direction_and_the_newest_translation() {
+        let history = history();
+        let index = history.embedding_index();
+        record(&history, "cat", "猫", "en");
+        record(&history, "cat", "猫咪", "en");
+        record(&history, "dog", "狗", "en");
+        record(&history, "chat", "猫", "fr");
+        index
+            .store(
+                MODEL,
+                &[
+                    ("cat".to_string(), vec![1.0, 0.0]),
+                    ("dog".to_string(), vec![0.0, 1.0]),
+                    ("chat".to_string(), vec![1.0, 0.0]),
+                ],
+            )
+            .expect("store");
+        let matches = index
+            .nearest(&[2.0, 0.1], "en", "zh", &settings())
+            .expect("nearest");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].source_text, "cat");
+        assert_eq!(matches[0].target_text, "猫咪");
+    }
+}
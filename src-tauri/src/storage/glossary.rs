@@ -0,0 +1,306 @@
+use super::{write_atomic, AUTO_LANG};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+const HEADER_NAMES: &[&str] = &["term", "source", "术语", "原文"];
+
+/// A required rendering of `term` for one language pair.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GlossaryEntry {
+    pub source_lang: String,
+    pub target_lang: String,
+    pub term: String,
+    pub translation: String,
+}
+
+/// A glossary term that appeared in the source but whose required rendering is missing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GlossaryViolation {
+    pub term: String,
+    pub expected: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GlossaryError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid glossary file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("line {line}: {message}")]
+    Parse { line: usize, message: String },
+}
+
+/// Term lists per language pair, persisted as JSON.
+#[derive(Clone)]
+pub struct GlossaryStore {
+    path: PathBuf,
+    entries: Arc<Mutex<Vec<GlossaryEntry>>>,
+}
+
+impl GlossaryStore {
+    pub fn open(path: PathBuf) -> Self {
+        let entries = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|err| {
+                warn!(?err, path = %path.display(), "ignoring unreadable glossary");
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self {
+            path,
+            entries: Arc::new(Mutex::new(entries)),
+        }
+    }
+
+    pub fn entries(&self) -> Vec<GlossaryEntry> {
+        self.entries.lock().expect("glossary lock").clone()
+    }
+
+    /// Imports `term,translation` rows from CSV or TSV text, replacing existing terms.
+    pub fn import(
+        &self,
+        content: &str,
+        source_lang: &str,
+        target_lang: &str,
+    ) -> Result<usize, GlossaryError> {
+        let rows = parse_delimited(content)?;
+        let mut entries = self.entries.lock().expect("glossary lock");
+        let mut imported = 0;
+
+        for row in rows {
+            let entry = GlossaryEntry {
+                source_lang: source_lang.to_string(),
+                target_lang: target_lang.to_string(),
+                term: row.0,
+                translation: row.1,
+            };
+            entries.retain(|existing| !same_term(existing, &entry));
+            entries.push(entry);
+            imported += 1;
+        }
+
+        self.persist(&entries)?;
+        Ok(imported)
+    }
+
+    pub fn remove(
+        &self,
+        source_lang: &str,
+        target_lang: &str,
+        term: &str,
+    ) -> Result<bool, GlossaryError> {
+        let mut entries = self.entries.lock().expect("glossary lock");
+        let before = entries.len();
+        entries.retain(|entry| {
+            !(entry.source_lang == source_lang
+                && entry.target_lang == target_lang
+                && entry.term == term)
+        });
+        let removed = entries.len() != before;
+        if removed {
+            self.persist(&entries)?;
+        }
+        Ok(removed)
+    }
+
+    /// Entries for the language pair whose term occurs in `text`.
+    pub fn matching(&self, text: &str, source_lang: &str, target_lang: &str) -> Vec<GlossaryEntry> {
+        self.entries
+            .lock()
+            .expect("glossary lock")
+            .iter()
+            .filter(|entry| entry.target_lang == target_lang)
+            .filter(|entry| source_lang == AUTO_LANG || entry.source_lang == source_lang)
+            .filter(|entry| contains_term(text, &entry.term))
+            .cloned()
+            .collect()
+    }
+
+    fn persist(&self, entries: &[GlossaryEntry]) -> Result<(), GlossaryError> {
+        let bytes = serde_json::to_vec_pretty(entries)?;
+        write_atomic(&self.path, &bytes)?;
+        Ok(())
+    }
+}
+
+/// Reports entries whose required translation does not appear in `translation`.
+pub fn check_translation(entries: &[GlossaryEntry], translation: &str) -> Vec<GlossaryViolation> {
+    entries
+        .iter()
+        .filter(|entry| !contains_term(translation, &entry.translation))
+        .map(|entry| GlossaryViolation {
+            term: entry.term.clone(),
+            expected: entry.translation.clone(),
+        })
+        .collect()
+}
+
+fn same_term(left: &GlossaryEntry, right: &GlossaryEntry) -> bool {
+    left.source_lang == right.source_lang
+        && left.target_lang == right.target_lang
+        && left.term.to_lowercase() == right.term.to_lowercase()
+}
+
+/// Case-insensitive search that respects word boundaries for alphanumeric terms.
+fn contains_term(haystack: &str, term: &str) -> bool {
+    let term = term.trim().to_lowercase();
+    if term.is_empty() {
+        return false;
+    }
+    let haystack = haystack.to_lowercase();
+    let is_word = |ch: char| ch.is_ascii_alphanumeric();
+
+    haystack.match_indices(&term).any(|(start, matched)| {
+        let end = start + matched.len();
+        let before = haystack[..start].chars().next_back();
+        let after = haystack[end..].chars().next();
+        let starts_word = term.chars().next().is_some_and(is_word);
+        let ends_word = term.chars().next_back().is_some_and(is_word);
+        let joined_before = starts_word && before.is_some_and(is_word);
+        let joined_after = ends_word && after.is_some_and(is_word);
+        !joined_before && !joined_after
+    })
+}
+
+/// Parses two-column CSV or TSV, detecting the delimiter from the first line.
+fn parse_delimited(content: &str) -> Result<Vec<(String, String)>, GlossaryError> {
+    let content = content.trim_start_matches('\u{feff}');
+    let first_line = content.lines().next().unwrap_or_default();
+    let delimiter = if first_line.contains('\t') { '\t' } else { ',' };
+    let mut rows = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = split_fields(line, delimiter).map_err(|message| GlossaryError::Parse {
+            line: index + 1,
+            message,
+        })?;
+        let mut fields = fields.into_iter().map(|field| field.trim().to_string());
+        let (Some(term), Some(translation)) = (fields.next(), fields.next()) else {
+            return Err(GlossaryError::Parse {
+                line: index + 1,
+                message: "expected term and translation columns".into(),
+            });
+        };
+        if index == 0 && HEADER_NAMES.contains(&term.to_lowercase().as_str()) {
+            continue;
+        }
+        if term.is_empty() || translation.is_empty() {
+            return Err(GlossaryError::Parse {
+                line: index + 1,
+                message: "empty term or translation".into(),
+            });
+        }
+        rows.push((term, translation));
+    }
+
+    Ok(rows)
+}
+
+fn split_fields(line: &str, delimiter: char) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if in_quotes => in_quotes = false,
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                in_quotes = true;
+            }
+            ch if ch == delimiter && !in_quotes => fields.push(std::mem::take(&mut field)),
+            ch => field.push(ch),
+        }
+    }
+
+    if in_quotes {
+        return Err("unterminated quoted field".into());
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(term: &str, translation: &str) -> GlossaryEntry {
+        GlossaryEntry {
+            source_lang: "en".to_string(),
+            target_lang: "zh".to_string(),
+            term: term.to_string(),
+            translation: translation.to_string(),
+        }
+    }
+
+    fn pairs(rows: &[(&str, &str)]) -> Vec<(String, String)> {
+        rows.iter()
+            .map(|(term, translation)| (term.to_string(), translation.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_csv_with_a_header_and_blank_lines() {
+        let rows = parse_delimited("\u{feff}term,translation\n\nkernel, 内核 \r\nshell,外壳\n")
+            .expect("parse");
+        assert_eq!(rows, pairs(&[("kernel", "内核"), ("shell", "外壳")]));
+    }
+
+    #[test]
+    fn parses_tsv_from_the_first_line() {
+        let rows = parse_delimited("a,b\tA, B\nc\tC").expect("parse");
+        assert_eq!(rows, pairs(&[("a,b", "A, B"), ("c", "C")]));
+    }
+
+    #[test]
+    fn quoted_fields_keep_delimiters_and_doubled_quotes() {
+        let rows = parse_delimited("\"hello, world\",\"say \"\"hi\"\"\"").expect("parse");
+        assert_eq!(rows, pairs(&[("hello, world", "say \"hi\"")]));
+    }
+
+    #[test]
+    fn malformed_rows_report_their_line() {
+        let error = |content: &str| match parse_delimited(content) {
+            Err(GlossaryError::Parse { line, .. }) => line,
+            other => panic!("expected a parse error, got {other:?}"),
+        };
+        assert_eq!(error("a,b\n\"open,b"), 2);
+        assert_eq!(error("a,b\nonly"), 2);
+        assert_eq!(error("a,\n"), 1);
+    }
+
+    #[test]
+    fn alphanumeric_terms_match_whole_words_only() {
+        assert!(contains_term("The API is ready.", "api"));
+        assert!(contains_term("api", "API"));
+        assert!(!contains_term("rapid growth", "api"));
+        assert!(!contains_term("APIs", "api"));
+        assert!(contains_term("使用内核模块", "内核"));
+        assert!(contains_term("C++ code", "c++"));
+        assert!(!contains_term("anything", "  "));
+    }
+
+    #[test]
+    fn check_translation_reports_missing_renderings() {
+        let entries = [entry("kernel", "内核"), entry("shell", "外壳")];
+        let violations = check_translation(&entries, "内核与命令行");
+        assert_eq!(
+            violations,
+            [GlossaryViolation {
+                term: "shell".to_string(),
+                expected: "外壳".to_string(),
+            }]
+        );
+        assert!(check_translation(&entries, "内核和外壳").is_empty());
+    }
+}
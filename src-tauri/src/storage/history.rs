@@ -0,0 +1,417 @@
+use super::EmbeddingIndex;
+use crate::platform_windows::SelectionSource;
+use chrono::{Local, TimeZone, Utc};
+use rusqlite::{params, Connection, Row};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+const MAX_PAGE_SIZE: u32 = 200;
+/// The trigram tokenizer cannot match shorter queries, which fall back to `LIKE`.
+const MIN_FTS_QUERY_CHARS: usize = 3;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source_text TEXT NOT NULL,
+    translated_text TEXT NOT NULL,
+    source_lang TEXT NOT NULL,
+    target_lang TEXT NOT NULL,
+    model TEXT NOT NULL,
+    selection_source TEXT,
+    created_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS history_created_at ON history (created_at);
+CREATE INDEX IF NOT EXISTS history_lang_pair ON history (source_lang, target_lang);
+CREATE VIRTUAL TABLE IF NOT EXISTS history_fts USING fts5(
+    source_text, translated_text,
+    content = 'history', content_rowid = 'id', tokenize = 'trigram'
+);
+CREATE TRIGGER IF NOT EXISTS history_ai AFTER INSERT ON history BEGIN
+    INSERT INTO history_fts (rowid, source_text, translated_text)
+    VALUES (new.id, new.source_text, new.translated_text);
+END;
+CREATE TRIGGER IF NOT EXISTS history_ad AFTER DELETE ON history BEGIN
+    INSERT INTO history_fts (history_fts, rowid, source_text, translated_text)
+    VALUES ('delete', old.id, old.source_text, old.translated_text);
+END;
+";
+
+const COLUMNS: &str = "history.id, history.source_text, history.translated_text, \
+    history.source_lang, history.target_lang, history.model, history.selection_source, \
+    history.created_at";
+
+#[derive(Debug, thiserror::Error)]
+pub enum HistoryError {
+    #[error("history database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("history export failed: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// One finished translation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub source_text: String,
+    pub translated_text: String,
+    pub source_lang: String,
+    pub target_lang: String,
+    pub model: String,
+    /// Unset for translations the user started by hand.
+    pub selection_source: Option<SelectionSource>,
+    /// Unix time in milliseconds.
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryPage {
+    pub entries: Vec<HistoryEntry>,
+    /// Matching entries across all pages.
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+}
+
+/// Translation history in SQLite, with an FTS5 index over both texts.
+#[derive(Clone)]
+pub struct HistoryStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl HistoryStore {
+    /// Opens the database at `path`, or an in-memory one if that fails.
+    pub fn open(path: &Path) -> Self {
+        let conn = Self::open_file(path).unwrap_or_else(|err| {
+            warn!(%err, path = %path.display(), "history database unavailable, keeping history in memory");
+            let conn = Connection::open_in_memory().expect("in-memory sqlite");
+            conn.execute_batch(SCHEMA).expect("history schema");
+            conn
+        });
+        Self {
+            conn: Arc::new(Mutex::new(conn)),
+        }
+    }
+
+    fn open_file(path: &Path) -> Result<Connection, HistoryError> {
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(conn)
+    }
+
+    /// Embedding vectors kept in the same database as the entries.
+    pub fn embedding_index(&self) -> EmbeddingIndex {
+        EmbeddingIndex::new(self.conn.clone())
+    }
+
+    /// Stores `entry`, ignoring its `id`, and returns the new id.
+    pub fn record(&self, entry: &HistoryEntry) -> Result<i64, HistoryError> {
+        let conn = self.conn.lock().expect("history lock");
+        conn.execute(
+            "INSERT INTO history (source_text, translated_text, source_lang, target_lang, \
+             model, selection_source, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                entry.source_text,
+                entry.translated_text,
+                entry.source_lang,
+                entry.target_lang,
+                entry.model,
+                entry.selection_source.as_ref().map(selection_source_name),
+                entry.created_at,
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Newest entries first.
+    pub fn list(&self, offset: u32, limit: u32) -> Result<HistoryPage, HistoryError> {
+        let conn = self.conn.lock().expect("history lock");
+        let total: u64 = conn.query_row("SELECT COUNT(*) FROM history", [], |row| row.get(0))?;
+        let mut statement = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM history ORDER BY history.id DESC LIMIT ?1 OFFSET ?2"
+        ))?;
+        let entries = statement
+            .query_map(params![limit.min(MAX_PAGE_SIZE), offset], read_entry)?
+            .collect::<Result<_, _>>()?;
+        Ok(HistoryPage { entries, total })
+    }
+
+    /// Entries whose source or translation contains `query`, newest first.
+    pub fn search(
+        &self,
+        query: &str,
+        offset: u32,
+        limit: u32,
+    ) -> Result<HistoryPage, HistoryError> {
+        let query = query.trim();
+        if query.is_empty() {
+            return self.list(offset, limit);
+        }
+        let (filter, pattern) = if query.chars().count() >= MIN_FTS_QUERY_CHARS {
+            (
+                "history.id IN (SELECT rowid FROM history_fts WHERE history_fts MATCH ?1)",
+                format!("\"{}\"", query.replace('"', "\"\"")),
+            )
+        } else {
+            (
+                "(history.source_text LIKE ?1 ESCAPE '\\' \
+                 OR history.translated_text LIKE ?1 ESCAPE '\\')",
+                format!(
+                    "%{}%",
+                    query
+                        .replace('\\', "\\\\")
+                        .replace('%', "\\%")
+                        .replace('_', "\\_")
+                ),
+            )
+        };
+
+        let conn = self.conn.lock().expect("history lock");
+        let total: u64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM history WHERE {filter}"),
+            params![pattern],
+            |row| row.get(0),
+        )?;
+        let mut statement = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM history WHERE {filter} \
+             ORDER BY history.id DESC LIMIT ?2 OFFSET ?3"
+        ))?;
+        let entries = statement
+            .query_map(
+                params![pattern, limit.min(MAX_PAGE_SIZE), offset],
+                read_entry,
+            )?
+            .collect::<Result<_, _>>()?;
+        Ok(HistoryPage { entries, total })
+    }
+
+    /// Deletes the given entries and returns how many existed.
+    pub fn delete(&self, ids: &[i64]) -> Result<usize, HistoryError> {
+        let mut conn = self.conn.lock().expect("history lock");
+        let transaction = conn.transaction()?;
+        let mut removed = 0;
+        for id in ids {
+            removed += transaction.execute("DELETE FROM history WHERE id = ?1", params![id])?;
+        }
+        transaction.commit()?;
+        Ok(removed)
+    }
+
+    pub fn clear(&self) -> Result<usize, HistoryError> {
+        let conn = self.conn.lock().expect("history lock");
+        Ok(conn.execute("DELETE FROM history", [])?)
+    }
+
+    /// The whole history, oldest first, as CSV with a header row or as JSON lines.
+    pub fn export(&self, format: ExportFormat) -> Result<String, HistoryError> {
+        let conn = self.conn.lock().expect("history lock");
+        let mut statement = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM history ORDER BY history.id ASC"
+        ))?;
+        let entries = statement.query_map([], read_entry)?;
+
+        let mut output = String::new();
+        if format == ExportFormat::Csv {
+            output.push_str(
+                "id,created_at,source_lang,target_lang,model,selection_source,source_text,translated_text\r\n",
+            );
+        }
+        for entry in entries {
+            let entry = entry?;
+            match format {
+                ExportFormat::Jsonl => {
+                    output.push_str(&serde_json::to_string(&entry)?);
+                    output.push('\n');
+                }
+                ExportFormat::Csv => {
+                    let created_at = Utc
+                        .timestamp_millis_opt(entry.created_at)
+                        .single()
+                        .map(|time| time.with_timezone(&Local).to_rfc3339())
+                        .unwrap_or_default();
+                    let fields = [
+                        entry.id.to_string(),
+                        created_at,
+                        entry.source_lang,
+                        entry.target_lang,
+                        entry.model,
+                        entry
+                            .selection_source
+                            .as_ref()
+                            .map(selection_source_name)
+                            .unwrap_or_default(),
+                        entry.source_text,
+                        entry.translated_text,
+                    ];
+                    let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+                    output.push_str(&row.join(","));
+                    output.push_str("\r\n");
+                }
+            }
+        }
+        Ok(output)
+    }
+}
+
+fn read_entry(row: &Row<'_>) -> rusqlite::Result<HistoryEntry> {
+    let selection_source: Option<String> = row.get(6)?;
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        source_text: row.get(1)?,
+        translated_text: row.get(2)?,
+        source_lang: row.get(3)?,
+        target_lang: row.get(4)?,
+        model: row.get(5)?,
+        selection_source: selection_source
+            .and_then(|name| serde_json::from_value(serde_json::Value::String(name)).ok()),
+        created_at: row.get(7)?,
+    })
+}
+
+fn selection_source_name(source: &SelectionSource) -> String {
+    match serde_json::to_value(source) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => format!("{source:?}"),
+    }
+}
+
+/// Cells that a spreadsheet would read as a formula get a leading `'`.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{value}")
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> HistoryStore {
+        HistoryStore::open(Path::new(":memory:"))
+    }
+
+    fn record(store: &HistoryStore, source_text: &str, translated_text: &str) -> i64 {
+        store
+            .record(&HistoryEntry {
+                id: 0,
+                source_text: source_text.to_string(),
+                translated_text: translated_text.to_string(),
+                source_lang: "en".to_string(),
+                target_lang: "zh".to_string(),
+                model: "model".to_string(),
+                selection_source: None,
+                created_at: 0,
+            })
+            .unwrap()
+    }
+
+    fn found(store: &HistoryStore, query: &str) -> Vec<String> {
+        store
+            .search(query, 0, 50)
+            .unwrap()
+            .entries
+            .into_iter()
+            .map(|entry| entry.source_text)
+            .collect()
+    }
+
+    #[test]
+    fn long_queries_search_both_texts_through_the_index() {
+        let store = store();
+        record(&store, "The quick brown fox", "敏捷的棕色狐狸");
+        record(&store, "A lazy dog", "一只懒狗");
+        record(&store, "Quick thinking", "快速思考");
+
+        assert_eq!(
+            found(&store, "quick"),
+            ["Quick thinking", "The quick brown fox"]
+        );
+        assert_eq!(found(&store, "棕色狐"), ["The quick brown fox"]);
+        assert_eq!(found(&store, "say \"hi"), Vec::<String>::new());
+        assert_eq!(store.search("quick", 0, 1).unwrap().total, 2);
+    }
+
+    #[test]
+    fn short_queries_fall_back_to_like() {
+        let store = store();
+        record(&store, "ox", "牛");
+        record(&store, "box", "盒子");
+        record(&store, "dog", "狗");
+
+        assert_eq!(found(&store, "ox"), ["box", "ox"]);
+        assert_eq!(found(&store, "狗"), ["dog"]);
+    }
+
+    #[test]
+    fn like_wildcards_in_short_queries_match_literally() {
+        let store = store();
+        record(&store, "100%", "百分之百");
+        record(&store, "a_b", "下划线");
+        record(&store, "c\\d", "反斜杠");
+        record(&store, "plain", "普通");
+
+        assert_eq!(found(&store, "%"), ["100%"]);
+        assert_eq!(found(&store, "_"), ["a_b"]);
+        assert_eq!(found(&store, "\\"), ["c\\d"]);
+    }
+
+    #[test]
+    fn deleted_entries_leave_the_list_and_the_index() {
+        let store = store();
+        let keep = record(&store, "keep this", "保留");
+        let drop = record(&store, "drop this", "删除");
+
+        assert_eq!(store.delete(&[drop, drop + 100]).unwrap(), 1);
+        let page = store.list(0, 50).unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.entries[0].id, keep);
+        assert!(found(&store, "drop").is_empty());
+        assert_eq!(found(&store, "this"), ["keep this"]);
+    }
+
+    #[test]
+    fn csv_export_quotes_and_neutralizes_cells() {
+        let store = store();
+        record(&store, "Hello, \"world\"\nagain", "=SUM(A1)");
+        record(&store, "@user", "-1");
+
+        let csv = store.export(ExportFormat::Csv).unwrap();
+        let rows: Vec<&str> = csv.split("\r\n").collect();
+        assert!(rows[0].starts_with("id,created_at,"));
+        assert!(rows[1].ends_with(",\"Hello, \"\"world\"\"\nagain\",'=SUM(A1)"));
+        assert!(rows[2].ends_with(",'@user,'-1"));
+        assert_eq!(rows[3], "");
+    }
+
+    #[test]
+    fn jsonl_export_writes_one_escaped_object_per_line() {
+        let store = store();
+        record(&store, "line one\nline \"two\"", "=1");
+        record(&store, "second", "第二");
+
+        let jsonl = store.export(ExportFormat::Jsonl).unwrap();
+        let lines: Vec<&str> = jsonl.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let first: HistoryEntry = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first.source_text, "line one\nline \"two\"");
+        assert_eq!(first.translated_text, "=1");
+        let second: HistoryEntry = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(second.translated_text, "第二");
+    }
+}
@@ -1,68 +1,250 @@
+use crate::api_client::{RetryPolicy, SILICONFLOW_PROVIDER};
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+mod anki;
+mod budget;
+mod cache;
+mod credentials;
+mod embedding_index;
+mod glossary;
+mod history;
+mod prompt;
+mod settings_file;
+mod tmx;
+mod translation_memory;
+mod usage;
+mod validation;
+mod vocabulary;
+
+pub use budget::{BudgetAction, BudgetMetric, BudgetSettings, BudgetUsage, BudgetWarning};
+pub use cache::{CacheError, CacheSettings, CacheStore, CachedTranslation};
+pub use credentials::{
+    ApiKeyStatus, CredentialBackendStatus, CredentialError, CredentialStore, DEFAULT_KEY_NAME,
+};
+pub use embedding_index::{EmbeddingIndex, SemanticRetrievalSettings, MAX_EMBEDDED_CHARS};
+pub use glossary::{
+    check_translation, GlossaryEntry, GlossaryError, GlossaryStore, GlossaryViolation,
+};
+pub use history::{ExportFormat, HistoryEntry, HistoryError, HistoryPage, HistoryStore};
+pub use prompt::{
+    default_profiles, PromptError, PromptProfile, PromptVariables, DEFAULT_PROMPT_PROFILE,
+};
+pub use settings_file::{SettingsError, SettingsStore};
+pub use translation_memory::{
+    MemoryMatch, TranslationMemoryError, TranslationMemorySettings, TranslationMemoryStore,
+    TranslationUnit,
+};
+pub use usage::{estimate_cost, ModelPrice, UsageStats, UsageStore};
+pub use validation::SettingsValidationError;
+pub use vocabulary::{
+    NewVocabularyEntry, VocabularyEntry, VocabularyError, VocabularyStore, DEFAULT_DECK_NAME,
+};
+
+/// Source language value meaning "detect automatically".
+pub const AUTO_LANG: &str = "自动";
+
+const APP_DIR_NAME: &str = "silicon_translater";
+
+/// Directory holding settings and other user data; falls back to the working directory.
+pub fn app_config_dir() -> PathBuf {
+    tauri::api::path::config_dir()
+        .map(|dir| dir.join(APP_DIR_NAME))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Directory for data that can be thrown away, such as cached translations.
+pub fn app_cache_dir() -> PathBuf {
+    tauri::api::path::cache_dir()
+        .map(|dir| dir.join(APP_DIR_NAME))
+        .unwrap_or_else(|| app_config_dir().join("cache"))
+}
+
+/// Writes `bytes` to a sibling temp file and renames it over `path`.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = tmp_path(path);
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Like `write_atomic`, but the file is only ever readable by its owner on
+/// unix, including the moment before the rename.
+pub fn write_atomic_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = tmp_path(path);
+    // A leftover file would keep its old permissions, so never reuse one.
+    match std::fs::remove_file(&tmp) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&tmp, path)
+}
+
+/// Renames an unreadable file to `<path>.corrupt`, so the next save does not destroy it.
+pub(crate) fn quarantine(path: &Path) -> std::io::Result<()> {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".corrupt");
+    std::fs::rename(path, backup)
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    PathBuf::from(tmp)
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppSettings {
     pub model: String,
     pub source_lang: String,
     pub target_lang: String,
+    /// Used instead of `target_lang` when the source text is already in the target language.
+    pub secondary_target_lang: String,
     pub enable_detection: bool,
     pub selection_min_len: usize,
     pub selection_max_len: usize,
     pub debounce_ms: u64,
+    /// Texts estimated above this many tokens are split into segments.
+    pub segment_token_budget: usize,
+    pub segment_concurrency: usize,
+    /// Extra attempts for a segment that comes back empty.
+    pub segment_max_retries: u32,
+    pub retry: RetryPolicy,
+    pub network: NetworkSettings,
+    pub provider: String,
+    pub custom_providers: Vec<ProviderConfig>,
+    pub prompt_profiles: Vec<PromptProfile>,
+    pub active_prompt_profile: String,
+    /// Used to estimate the cost of recorded token usage.
+    pub model_prices: Vec<ModelPrice>,
+    pub budget: BudgetSettings,
+    pub cache: CacheSettings,
+    /// Record finished translations in the searchable history.
+    pub history_enabled: bool,
+    pub translation_memory: TranslationMemorySettings,
+    pub semantic_retrieval: SemanticRetrievalSettings,
 }
 
-impl Default for AppSettings {
+/// An extra OpenAI-compatible backend, such as an internal gateway.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderConfig {
+    pub name: String,
+    pub endpoint: String,
+}
+
+/// How requests reach the provider, e.g. through a corporate proxy or gateway.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// Replaces the selected provider's base URL, e.g. `https://gateway.example.com/v1`.
+    pub base_url: Option<String>,
+    /// Extra headers sent with every request.
+    pub headers: Vec<HeaderConfig>,
+    /// `http://`, `https://` or `socks5://` URL; the system proxy is used when unset.
+    pub proxy: Option<String>,
+    /// PEM file with extra root certificates.
+    pub ca_bundle: Option<PathBuf>,
+    pub connect_timeout_ms: u64,
+    pub request_timeout_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeaderConfig {
+    pub name: String,
+    pub value: String,
+}
+
+impl Default for NetworkSettings {
     fn default() -> Self {
         Self {
-            model: "Qwen/Qwen2.5-7B-Instruct".to_string(),
-            source_lang: "自动".to_string(),
-            target_lang: "中文".to_string(),
-            enable_detection: true,
-            selection_min_len: 1,
-            selection_max_len: 5000,
-            debounce_ms: 200,
+            base_url: None,
+            headers: Vec::new(),
+            proxy: None,
+            ca_bundle: None,
+            connect_timeout_ms: 10_000,
+            request_timeout_ms: 20_000,
         }
     }
 }
 
-#[derive(Clone)]
-pub struct CredentialStore;
+impl NetworkSettings {
+    /// Blank fields left by the settings form count as unset.
+    pub fn base_url(&self) -> Option<&str> {
+        non_blank(self.base_url.as_deref())
+    }
 
-impl CredentialStore {
-    pub fn new() -> Self {
-        Self
+    pub fn proxy(&self) -> Option<&str> {
+        non_blank(self.proxy.as_deref())
     }
 
-    pub fn write_api_key(&self, api_key: &str) -> Result<(), CredentialError> {
-        #[cfg(target_os = "windows")]
-        {
-            return windows_impl::write_credential(api_key);
-        }
-        #[cfg(not(target_os = "windows"))]
-        {
-            let _ = api_key;
-            return Err(CredentialError::Unsupported);
-        }
+    pub fn ca_bundle(&self) -> Option<&Path> {
+        self.ca_bundle
+            .as_deref()
+            .filter(|path| !path.as_os_str().is_empty())
     }
+}
 
-    pub fn read_api_key(&self) -> Result<Option<String>, CredentialError> {
-        #[cfg(target_os = "windows")]
-        {
-            return windows_impl::read_credential();
-        }
-        #[cfg(not(target_os = "windows"))]
-        {
-            return Ok(None);
+fn non_blank(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|value| !value.is_empty())
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            model: "Qwen/Qwen2.5-7B-Instruct".to_string(),
+            source_lang: AUTO_LANG.to_string(),
+            target_lang: "中文".to_string(),
+            secondary_target_lang: "English".to_string(),
+            enable_detection: true,
+            selection_min_len: 1,
+            selection_max_len: 5000,
+            debounce_ms: 200,
+            segment_token_budget: 800,
+            segment_concurrency: 3,
+            segment_max_retries: 2,
+            retry: RetryPolicy::default(),
+            network: NetworkSettings::default(),
+            provider: SILICONFLOW_PROVIDER.to_string(),
+            custom_providers: Vec::new(),
+            prompt_profiles: default_profiles(),
+            active_prompt_profile: DEFAULT_PROMPT_PROFILE.to_string(),
+            model_prices: Vec::new(),
+            budget: BudgetSettings::default(),
+            cache: CacheSettings::default(),
+            history_enabled: true,
+            translation_memory: TranslationMemorySettings::default(),
+            semantic_retrieval: SemanticRetrievalSettings::default(),
         }
     }
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum CredentialError {
-    #[error("unsupported platform")]
-    Unsupported,
-    #[error("windows error: {0}")]
-    Windows(String),
+impl AppSettings {
+    pub fn active_prompt(&self) -> PromptProfile {
+        self.prompt_profiles
+            .iter()
+            .find(|profile| profile.name == self.active_prompt_profile)
+            .cloned()
+            .unwrap_or_default()
+    }
 }
 
 #[cfg(target_os = "windows")]
@@ -0,0 +1,338 @@
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PROMPT_PROFILE: &str = "通用";
+
+const PLACEHOLDERS: &[&str] = &["source_lang", "target_lang", "text", "context"];
+const REQUIRED_PLACEHOLDER: &str = "text";
+
+/// A named pair of system/user templates.
+///
+/// Templates may use `{source_lang}`, `{target_lang}`, `{text}` and `{context}`;
+/// `{{` and `}}` produce literal braces.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptProfile {
+    pub name: String,
+    pub system_template: String,
+    pub user_template: String,
+}
+
+/// Values substituted into a `PromptProfile`.
+#[derive(Debug, Clone, Default)]
+pub struct PromptVariables<'a> {
+    pub source_lang: &'a str,
+    pub target_lang: &'a str,
+    pub text: &'a str,
+    pub context: &'a str,
+}
+
+const SYSTEM_TEMPLATE: &str = "system_template";
+const USER_TEMPLATE: &str = "user_template";
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PromptError {
+    #[error("profile {profile}: {template} must contain {{{placeholder}}}")]
+    MissingPlaceholder {
+        profile: String,
+        template: &'static str,
+        placeholder: &'static str,
+    },
+    #[error("profile {profile}: {template} must contain {{{placeholder}}} only once")]
+    DuplicatePlaceholder {
+        profile: String,
+        template: &'static str,
+        placeholder: &'static str,
+    },
+    #[error("profile {profile}: unknown placeholder {{{placeholder}}} in {template}")]
+    UnknownPlaceholder {
+        profile: String,
+        template: &'static str,
+        placeholder: String,
+    },
+    #[error("profile {profile}: unbalanced brace in {template}")]
+    UnbalancedBrace {
+        profile: String,
+        template: &'static str,
+    },
+    #[error("unknown prompt profile: {0}")]
+    UnknownProfile(String),
+}
+
+impl PromptError {
+    /// The `PromptProfile` field the error refers to.
+    pub fn field(&self) -> &'static str {
+        match self {
+            Self::MissingPlaceholder { template, .. }
+            | Self::DuplicatePlaceholder { template, .. }
+            | Self::UnknownPlaceholder { template, .. }
+            | Self::UnbalancedBrace { template, .. } => template,
+            Self::UnknownProfile(_) => "name",
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::MissingPlaceholder { .. } => "missing_placeholder",
+            Self::DuplicatePlaceholder { .. } => "duplicate_placeholder",
+            Self::UnknownPlaceholder { .. } => "unknown_placeholder",
+            Self::UnbalancedBrace { .. } => "unbalanced_brace",
+            Self::UnknownProfile(_) => "unknown_profile",
+        }
+    }
+}
+
+enum Token<'a> {
+    Literal(&'a str),
+    Placeholder(&'a str),
+}
+
+impl PromptProfile {
+    pub fn validate(&self) -> Result<(), PromptError> {
+        for (template, field) in [
+            (&self.system_template, SYSTEM_TEMPLATE),
+            (&self.user_template, USER_TEMPLATE),
+        ] {
+            let placeholders = self.placeholders(template, field)?;
+            if let Some(unknown) = placeholders
+                .iter()
+                .find(|name| !PLACEHOLDERS.contains(name))
+            {
+                return Err(PromptError::UnknownPlaceholder {
+                    profile: self.name.clone(),
+                    template: field,
+                    placeholder: unknown.to_string(),
+                });
+            }
+            if field == USER_TEMPLATE {
+                // The text is sent exactly once; a second copy doubles the cost.
+                match placeholders
+                    .iter()
+                    .filter(|name| **name == REQUIRED_PLACEHOLDER)
+                    .count()
+                {
+                    0 => {
+                        return Err(PromptError::MissingPlaceholder {
+                            profile: self.name.clone(),
+                            template: field,
+                            placeholder: REQUIRED_PLACEHOLDER,
+                        })
+                    }
+                    1 => {}
+                    _ => {
+                        return Err(PromptError::DuplicatePlaceholder {
+                            profile: self.name.clone(),
+                            template: field,
+                            placeholder: REQUIRED_PLACEHOLDER,
+                        })
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the rendered `(system, user)` prompts.
+    pub fn render(&self, variables: &PromptVariables<'_>) -> (String, String) {
+        (
+            self.render_template(&self.system_template, SYSTEM_TEMPLATE, variables),
+            self.render_template(&self.user_template, USER_TEMPLATE, variables),
+        )
+    }
+
+    fn render_template(
+        &self,
+        template: &str,
+        field: &'static str,
+        variables: &PromptVariables<'_>,
+    ) -> String {
+        let Ok(tokens) = self.tokenize(template, field) else {
+            return template.to_string();
+        };
+
+        let mut output = String::with_capacity(template.len() + variables.text.len());
+        for token in tokens {
+            match token {
+                Token::Literal(literal) => output.push_str(literal),
+                Token::Placeholder("source_lang") => output.push_str(variables.source_lang),
+                Token::Placeholder("target_lang") => output.push_str(variables.target_lang),
+                Token::Placeholder("text") => output.push_str(variables.text),
+                Token::Placeholder("context") => output.push_str(variables.context),
+                Token::Placeholder(_) => {}
+            }
+        }
+        output
+    }
+
+    fn placeholders<'a>(
+        &self,
+        template: &'a str,
+        field: &'static str,
+    ) -> Result<Vec<&'a str>, PromptError> {
+        Ok(self
+            .tokenize(template, field)?
+            .into_iter()
+            .filter_map(|token| match token {
+                Token::Placeholder(name) => Some(name),
+                Token::Literal(_) => None,
+            })
+            .collect())
+    }
+
+    fn tokenize<'a>(
+        &self,
+        template: &'a str,
+        field: &'static str,
+    ) -> Result<Vec<Token<'a>>, PromptError> {
+        let unbalanced = || PromptError::UnbalancedBrace {
+            profile: self.name.clone(),
+            template: field,
+        };
+        let mut tokens = Vec::new();
+        let mut rest = template;
+
+        while let Some(pos) = rest.find(['{', '}']) {
+            if pos > 0 {
+                tokens.push(Token::Literal(&rest[..pos]));
+            }
+            let tail = &rest[pos..];
+            if let Some(after) = tail.strip_prefix("{{") {
+                tokens.push(Token::Literal("{"));
+                rest = after;
+            } else if let Some(after) = tail.strip_prefix("}}") {
+                tokens.push(Token::Literal("}"));
+                rest = after;
+            } else if tail.starts_with('}') {
+                return Err(unbalanced());
+            } else {
+                let end = tail.find('}').ok_or_else(unbalanced)?;
+                let name = &tail[1..end];
+                if name.contains('{') {
+                    return Err(unbalanced());
+                }
+                tokens.push(Token::Placeholder(name.trim()));
+                rest = &tail[end + 1..];
+            }
+        }
+
+        if !rest.is_empty() {
+            tokens.push(Token::Literal(rest));
+        }
+        Ok(tokens)
+    }
+}
+
+impl Default for PromptProfile {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_PROMPT_PROFILE.to_string(),
+            system_template: "你是专业翻译。忠实准确，保持术语一致，不扩写不发挥。只输出译文。"
+                .to_string(),
+            user_template: "将以下文本从 {source_lang} 翻译为 {target_lang}:\n{text}".to_string(),
+        }
+    }
+}
+
+pub fn default_profiles() -> Vec<PromptProfile> {
+    vec![
+        PromptProfile::default(),
+        PromptProfile {
+            name: "技术文档".to_string(),
+            system_template: "你是资深技术文档译者。保留代码、命令、路径、API 名称和格式标记原样不译，术语使用业界通行译法。只输出译文。"
+                .to_string(),
+            user_template: "将以下技术文档从 {source_lang} 翻译为 {target_lang}:\n{text}"
+                .to_string(),
+        },
+        PromptProfile {
+            name: "日常闲聊".to_string(),
+            system_template: "你是母语级的口语译者。译文自然地道、语气贴近原文，可适度意译。只输出译文。"
+                .to_string(),
+            user_template: "把这段话从 {source_lang} 翻成 {target_lang}:\n{text}".to_string(),
+        },
+        PromptProfile {
+            name: "法律".to_string(),
+            system_template: "你是法律文本译者。严格逐句对应，措辞正式严谨，不省略、不增补、不改变任何条件和限定。只输出译文。"
+                .to_string(),
+            user_template: "将以下法律文本从 {source_lang} 翻译为 {target_lang}:\n{text}"
+                .to_string(),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(system_template: &str, user_template: &str) -> PromptProfile {
+        PromptProfile {
+            name: "test".to_string(),
+            system_template: system_template.to_string(),
+            user_template: user_template.to_string(),
+        }
+    }
+
+    fn code(system_template: &str, user_template: &str) -> Option<&'static str> {
+        profile(system_template, user_template)
+            .validate()
+            .err()
+            .map(|err| err.code())
+    }
+
+    #[test]
+    fn builtin_profiles_are_valid() {
+        for profile in default_profiles() {
+            assert_eq!(profile.validate(), Ok(()), "{}", profile.name);
+        }
+    }
+
+    #[test]
+    fn the_user_template_needs_text_exactly_once() {
+        assert_eq!(code("", "{text}"), None);
+        assert_eq!(code("", "{ text }"), None);
+        assert_eq!(code("{text}", "translate"), Some("missing_placeholder"));
+        assert_eq!(code("", "{{text}}"), Some("missing_placeholder"));
+        assert_eq!(
+            code("", "{text} and again {text}"),
+            Some("duplicate_placeholder")
+        );
+
+        let err = profile("", "{text}{text}").validate().unwrap_err();
+        assert_eq!(err.field(), USER_TEMPLATE);
+        assert_eq!(
+            err.to_string(),
+            "profile test: user_template must contain {text} only once"
+        );
+    }
+
+    #[test]
+    fn unknown_placeholders_and_stray_braces_are_rejected() {
+        assert_eq!(code("{tone}", "{text}"), Some("unknown_placeholder"));
+        assert_eq!(code("", "{text} {}"), Some("unknown_placeholder"));
+        assert_eq!(code("{source_lang", "{text}"), Some("unbalanced_brace"));
+        assert_eq!(code("", "{text} }"), Some("unbalanced_brace"));
+        assert_eq!(code("", "{te{xt}"), Some("unbalanced_brace"));
+
+        let err = profile("oops }", "{text}").validate().unwrap_err();
+        assert_eq!(err.field(), SYSTEM_TEMPLATE);
+    }
+
+    #[test]
+    fn rendering_substitutes_variables_and_unescapes_braces() {
+        let profile = profile(
+            "Translate {source_lang} to {target_lang}. Use {{braces}}.",
+            "{context}\n---\n{ text }",
+        );
+        let (system, user) = profile.render(&PromptVariables {
+            source_lang: "en",
+            target_lang: "zh",
+            text: "{literal}",
+            context: "notes",
+        });
+        assert_eq!(system, "Translate en to zh. Use {braces}.");
+        assert_eq!(user, "notes\n---\n{literal}");
+    }
+
+    #[test]
+    fn an_invalid_template_is_rendered_verbatim() {
+        let (system, _) = profile("broken {", "{text}").render(&PromptVariables::default());
+        assert_eq!(system, "broken {");
+    }
+}
@@ -0,0 +1,310 @@
+use super::{quarantine, write_atomic, AppSettings, PromptError, SettingsValidationError};
+use crate::api_client::HttpConfigError;
+use serde_json::{Map, Value};
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+/// Version written into new settings files; it grows with each entry in `MIGRATIONS`.
+pub const CURRENT_SCHEMA_VERSION: u64 = MIGRATIONS.len() as u64;
+
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// `MIGRATIONS[n]` upgrades a version `n` document to version `n + 1`; append
+/// a step on breaking changes to the layout.
+///
+/// Purely additive changes to `AppSettings` need no migration: missing fields
+/// fall back to their defaults when the document is deserialized.
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[];
+
+/// Rounds of resetting invalid fields before a loaded file is given up on;
+/// a reset can expose another error, such as an active profile that is gone.
+const MAX_REPAIR_PASSES: usize = 3;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SettingsError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid settings file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("settings file is not a JSON object")]
+    NotAnObject,
+    #[error(transparent)]
+    Prompt(#[from] PromptError),
+    #[error(transparent)]
+    Invalid(#[from] SettingsValidationError),
+    /// The settings are valid but the HTTP client could not be built from
+    /// them, for example because the CA bundle is unreadable.
+    #[error(transparent)]
+    Network(#[from] HttpConfigError),
+}
+
+/// Reads and writes `AppSettings` as a versioned JSON file.
+#[derive(Debug, Clone)]
+pub struct SettingsStore {
+    path: PathBuf,
+}
+
+impl SettingsStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Loads the file, or returns defaults when it is missing or unreadable.
+    pub fn load_or_default(&self) -> AppSettings {
+        match self.load() {
+            Ok(Some(settings)) => settings,
+            Ok(None) => AppSettings::default(),
+            Err(err) => {
+                warn!(?err, path = %self.path.display(), "failed to load settings, using defaults");
+                if let Err(err) = quarantine(&self.path) {
+                    warn!(?err, "failed to move unreadable settings aside");
+                }
+                AppSettings::default()
+            }
+        }
+    }
+
+    pub fn load(&self) -> Result<Option<AppSettings>, SettingsError> {
+        let bytes = match std::fs::read(&self.path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let Value::Object(mut document) = serde_json::from_slice(&bytes)? else {
+            return Err(SettingsError::NotAnObject);
+        };
+        migrate(&mut document);
+        document.remove(SCHEMA_VERSION_KEY);
+        Ok(Some(repair(Value::Object(document))?))
+    }
+
+    pub fn save(&self, settings: &AppSettings) -> Result<(), SettingsError> {
+        let Value::Object(mut document) = serde_json::to_value(settings)? else {
+            return Err(SettingsError::NotAnObject);
+        };
+        document.insert(SCHEMA_VERSION_KEY.into(), CURRENT_SCHEMA_VERSION.into());
+        let bytes = serde_json::to_vec_pretty(&Value::Object(document))?;
+        write_atomic(&self.path, &bytes)?;
+        Ok(())
+    }
+}
+
+fn migrate(document: &mut Map<String, Value>) {
+    let mut version = document
+        .get(SCHEMA_VERSION_KEY)
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+
+    if version > CURRENT_SCHEMA_VERSION {
+        warn!(
+            version,
+            "settings written by a newer version, unknown fields are ignored"
+        );
+        return;
+    }
+
+    while let Some(step) = MIGRATIONS.get(version as usize) {
+        info!(from = version, to = version + 1, "migrating settings");
+        step(document);
+        version += 1;
+    }
+    document.insert(SCHEMA_VERSION_KEY.into(), version.into());
+}
+
+/// Deserializes a migrated document, resetting the fields that fail validation
+/// to their defaults so one bad value does not discard the whole file.
+fn repair(mut document: Value) -> Result<AppSettings, SettingsError> {
+    let defaults = serde_json::to_value(AppSettings::default())?;
+    let mut passes = 0;
+    loop {
+        let settings: AppSettings = serde_json::from_value(document.clone())?;
+        let Err(invalid) = settings.validate(None) else {
+            return Ok(settings);
+        };
+        passes += 1;
+        if passes > MAX_REPAIR_PASSES {
+            return Err(invalid.into());
+        }
+        let fields: Vec<&str> = invalid
+            .errors
+            .iter()
+            .map(|error| error.field.as_str())
+            .collect();
+        warn!(
+            ?fields,
+            "invalid settings, resetting them to their defaults"
+        );
+        for error in &invalid.errors {
+            reset_field(&mut document, &defaults, &error.field);
+        }
+    }
+}
+
+/// Replaces `field` with its default, or the closest enclosing value that has
+/// one, e.g. all of `custom_providers` for `custom_providers[0].endpoint`.
+fn reset_field(document: &mut Value, defaults: &Value, field: &str) {
+    let path = json_pointer(field);
+    let mut end = path.len();
+    while end > 0 {
+        let pointer = &path[..end];
+        if let (Some(slot), Some(default)) =
+            (document.pointer_mut(pointer), defaults.pointer(pointer))
+        {
+            *slot = default.clone();
+            return;
+        }
+        end = pointer.rfind('/').unwrap_or(0);
+    }
+}
+
+/// `prompt_profiles[1].user_template` becomes `/prompt_profiles/1/user_template`.
+fn json_pointer(field: &str) -> String {
+    field
+        .split('.')
+        .flat_map(|part| part.split('['))
+        .map(|part| format!("/{}", part.trim_end_matches(']')))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn defaults() -> Map<String, Value> {
+        match serde_json::to_value(AppSettings::default()).unwrap() {
+            Value::Object(document) => document,
+            _ => unreachable!(),
+        }
+    }
+
+    fn repaired(document: Map<String, Value>) -> Value {
+        serde_json::to_value(repair(Value::Object(document)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn every_known_version_migrates_to_the_current_one() {
+        for version in 0..=CURRENT_SCHEMA_VERSION {
+            let mut document = defaults();
+            document.insert(SCHEMA_VERSION_KEY.into(), version.into());
+            migrate(&mut document);
+            assert_eq!(
+                document.remove(SCHEMA_VERSION_KEY),
+                Some(CURRENT_SCHEMA_VERSION.into()),
+                "from version {version}"
+            );
+            assert_eq!(repaired(document), Value::Object(defaults()));
+        }
+    }
+
+    #[test]
+    fn unversioned_files_are_treated_as_version_zero() {
+        let mut document = Map::new();
+        document.insert("model".into(), "my-model".into());
+        migrate(&mut document);
+        assert_eq!(
+            document.get(SCHEMA_VERSION_KEY),
+            Some(&CURRENT_SCHEMA_VERSION.into())
+        );
+        assert_eq!(repaired(document)["model"], "my-model");
+    }
+
+    #[test]
+    fn newer_versions_are_left_alone() {
+        let mut document = defaults();
+        document.insert(
+            SCHEMA_VERSION_KEY.into(),
+            (CURRENT_SCHEMA_VERSION + 1).into(),
+        );
+        document.insert("added_later".into(), true.into());
+        let before = document.clone();
+        migrate(&mut document);
+        assert_eq!(document, before);
+    }
+
+    #[test]
+    fn invalid_fields_fall_back_to_their_defaults() {
+        let mut document = defaults();
+        document.insert("model".into(), "my-model".into());
+        document.insert("debounce_ms".into(), 999_999.into());
+        document["retry"]["jitter"] = json!(7.5);
+        document["prompt_profiles"][1]["user_template"] = json!("no text placeholder");
+
+        let settings = repaired(document);
+        let defaults = Value::Object(defaults());
+        assert_eq!(settings["model"], "my-model");
+        assert_eq!(settings["debounce_ms"], defaults["debounce_ms"]);
+        assert_eq!(settings["retry"], defaults["retry"]);
+        assert_eq!(settings["prompt_profiles"], defaults["prompt_profiles"]);
+    }
+
+    #[test]
+    fn an_unreadable_ca_bundle_is_kept() {
+        let missing = std::env::temp_dir().join("missing-ca-bundle.pem");
+        let mut document = defaults();
+        document["network"]["ca_bundle"] = json!(missing);
+        assert_eq!(repaired(document)["network"]["ca_bundle"], json!(missing));
+
+        let mut document = defaults();
+        document["network"]["ca_bundle"] = json!("relative/ca.pem");
+        assert_eq!(
+            repaired(document)["network"]["ca_bundle"],
+            Value::Object(defaults())["network"]["ca_bundle"]
+        );
+    }
+
+    #[test]
+    fn fields_without_a_default_reset_their_container() {
+        let mut document = defaults();
+        document.insert(
+            "custom_providers".into(),
+            json!([{ "name": "local", "endpoint": "ftp://localhost" }]),
+        );
+        assert_eq!(repaired(document)["custom_providers"], json!([]));
+    }
+
+    #[test]
+    fn resets_repeat_until_the_settings_are_consistent() {
+        let mut document = defaults();
+        document["prompt_profiles"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({
+                "name": "mine",
+                "system_template": "",
+                "user_template": "missing the placeholder",
+            }));
+        document.insert("active_prompt_profile".into(), "mine".into());
+
+        let settings = repaired(document);
+        let defaults = Value::Object(defaults());
+        assert_eq!(settings["prompt_profiles"], defaults["prompt_profiles"]);
+        assert_eq!(
+            settings["active_prompt_profile"],
+            defaults["active_prompt_profile"]
+        );
+    }
+
+    #[test]
+    fn unrepairable_settings_are_rejected() {
+        let mut document = defaults();
+        document.insert("selection_min_len".into(), 10.into());
+        document.insert("selection_max_len".into(), 0.into());
+        assert!(matches!(
+            repair(Value::Object(document)),
+            Err(SettingsError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn field_paths_become_json_pointers() {
+        assert_eq!(json_pointer("model"), "/model");
+        assert_eq!(json_pointer("retry.jitter"), "/retry/jitter");
+        assert_eq!(
+            json_pointer("prompt_profiles[1].user_template"),
+            "/prompt_profiles/1/user_template"
+        );
+        assert_eq!(json_pointer("budget.warn_at[0]"), "/budget/warn_at/0");
+    }
+}
@@ -0,0 +1,355 @@
+//! Reads and writes TMX 1.4 documents for the translation memory.
+
+use super::translation_memory::{TranslationMemoryError, TranslationUnit};
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+
+/// UI language names and the codes TMX files use for them.
+const LANGUAGE_CODES: &[(&str, &str)] = &[
+    ("中文", "zh"),
+    ("English", "en"),
+    ("日本語", "ja"),
+    ("한국어", "ko"),
+    ("Русский", "ru"),
+    ("العربية", "ar"),
+    ("ไทย", "th"),
+    ("Français", "fr"),
+    ("Deutsch", "de"),
+    ("Español", "es"),
+    ("Italiano", "it"),
+    ("Português", "pt"),
+];
+const DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+/// Inline elements that hold native markup instead of translatable text.
+const CODE_ELEMENTS: &[&[u8]] = &[b"bpt", b"ept", b"it", b"ph", b"ut"];
+
+#[derive(Default)]
+struct UnitBuilder {
+    source_lang: Option<String>,
+    updated_at: Option<i64>,
+    /// `(language code, segment text)` per `<tuv>`.
+    variants: Vec<(String, String)>,
+}
+
+impl UnitBuilder {
+    /// Pairs the source variant with every other language in the unit.
+    fn finish(self, header_lang: Option<&str>, now: i64) -> Vec<TranslationUnit> {
+        let source_lang = self.source_lang.as_deref().or(header_lang);
+        // An exact code wins; otherwise `en` names an `en-US` variant and vice versa.
+        let source = source_lang
+            .and_then(|lang| {
+                self.variants
+                    .iter()
+                    .find(|(code, _)| code.eq_ignore_ascii_case(lang))
+                    .or_else(|| {
+                        self.variants.iter().find(|(code, _)| {
+                            primary_subtag(code).eq_ignore_ascii_case(primary_subtag(lang))
+                        })
+                    })
+            })
+            .or(self.variants.first());
+        let Some((source_code, source_text)) = source else {
+            return Vec::new();
+        };
+        self.variants
+            .iter()
+            .filter(|(code, _)| {
+                !primary_subtag(code).eq_ignore_ascii_case(primary_subtag(source_code))
+            })
+            .map(|(code, text)| TranslationUnit {
+                source_text: source_text.clone(),
+                target_text: text.clone(),
+                source_lang: language_name(source_code),
+                target_lang: language_name(code),
+                updated_at: self.updated_at.unwrap_or(now),
+            })
+            .collect()
+    }
+}
+
+pub(super) fn parse(xml: &str, now: i64) -> Result<Vec<TranslationUnit>, TranslationMemoryError> {
+    let mut reader = Reader::from_str(xml);
+    let mut found_root = false;
+    let mut header_lang = None;
+    let mut unit: Option<UnitBuilder> = None;
+    let mut variant_lang = None;
+    let mut segment: Option<String> = None;
+    let mut code_depth = 0_usize;
+    let mut units = Vec::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => match element.local_name().as_ref() {
+                b"tmx" => found_root = true,
+                b"header" => header_lang = header_source_lang(&element)?,
+                b"tu" => {
+                    unit = Some(UnitBuilder {
+                        source_lang: attribute(&element, b"srclang")?,
+                        updated_at: unit_date(&element)?,
+                        variants: Vec::new(),
+                    })
+                }
+                b"tuv" => variant_lang = attribute(&element, b"lang")?,
+                b"seg" => segment = Some(String::new()),
+                name if segment.is_some() && CODE_ELEMENTS.contains(&name) => code_depth += 1,
+                _ => {}
+            },
+            Event::Empty(element) if element.local_name().as_ref() == b"header" => {
+                header_lang = header_source_lang(&element)?;
+            }
+            Event::Text(text) if code_depth == 0 => {
+                if let Some(segment) = segment.as_mut() {
+                    segment.push_str(&text.unescape()?);
+                }
+            }
+            Event::CData(text) if code_depth == 0 => {
+                if let Some(segment) = segment.as_mut() {
+                    segment.push_str(&String::from_utf8_lossy(&text));
+                }
+            }
+            Event::End(element) => match element.local_name().as_ref() {
+                b"seg" => {
+                    if let (Some(unit), Some(lang), Some(text)) =
+                        (unit.as_mut(), variant_lang.clone(), segment.take())
+                    {
+                        unit.variants.push((lang, text));
+                    }
+                }
+                b"tuv" => variant_lang = None,
+                b"tu" => {
+                    if let Some(unit) = unit.take() {
+                        units.extend(unit.finish(header_lang.as_deref(), now));
+                    }
+                }
+                name if CODE_ELEMENTS.contains(&name) => code_depth = code_depth.saturating_sub(1),
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if !found_root {
+        return Err(TranslationMemoryError::Invalid(
+            "missing <tmx> root element",
+        ));
+    }
+    Ok(units)
+}
+
+pub(super) fn write(units: &[TranslationUnit]) -> Result<String, TranslationMemoryError> {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    writer.write_event(Event::Start(
+        BytesStart::new("tmx").with_attributes([("version", "1.4")]),
+    ))?;
+    writer.write_event(Event::Empty(BytesStart::new("header").with_attributes([
+        ("creationtool", "silicon_translater"),
+        ("creationtoolversion", env!("CARGO_PKG_VERSION")),
+        ("segtype", "sentence"),
+        ("o-tmf", "silicon_translater"),
+        ("adminlang", "en"),
+        ("srclang", "*all*"),
+        ("datatype", "plaintext"),
+    ])))?;
+    writer.write_event(Event::Start(BytesStart::new("body")))?;
+    for unit in units {
+        let source_lang = language_code(&unit.source_lang);
+        let changed = Utc
+            .timestamp_millis_opt(unit.updated_at)
+            .single()
+            .map(|time| time.format(DATE_FORMAT).to_string())
+            .unwrap_or_default();
+        writer.write_event(Event::Start(BytesStart::new("tu").with_attributes([
+            ("srclang", source_lang.as_str()),
+            ("changedate", changed.as_str()),
+        ])))?;
+        for (lang, text) in [
+            (source_lang.as_str(), &unit.source_text),
+            (language_code(&unit.target_lang).as_str(), &unit.target_text),
+        ] {
+            writer
+                .create_element("tuv")
+                .with_attribute(("xml:lang", lang))
+                .write_inner_content::<_, quick_xml::Error>(|writer| {
+                    writer
+                        .create_element("seg")
+                        .write_text_content(BytesText::new(text))?;
+                    Ok(())
+                })?;
+        }
+        writer.write_event(Event::End(BytesEnd::new("tu")))?;
+    }
+    writer.write_event(Event::End(BytesEnd::new("body")))?;
+    writer.write_event(Event::End(BytesEnd::new("tmx")))?;
+    Ok(String::from_utf8_lossy(&writer.into_inner()).into_owned())
+}
+
+/// Matches `xml:lang` as well as the plain `lang` of TMX 1.1.
+fn attribute(element: &BytesStart, name: &[u8]) -> Result<Option<String>, TranslationMemoryError> {
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(quick_xml::Error::from)?;
+        if attribute.key.local_name().as_ref() == name {
+            return Ok(Some(attribute.unescape_value()?.into_owned()));
+        }
+    }
+    Ok(None)
+}
+
+/// `*all*` means any variant may serve as the source.
+fn header_source_lang(element: &BytesStart) -> Result<Option<String>, TranslationMemoryError> {
+    Ok(attribute(element, b"srclang")?.filter(|lang| lang != "*all*"))
+}
+
+fn unit_date(element: &BytesStart) -> Result<Option<i64>, TranslationMemoryError> {
+    let date = match attribute(element, b"changedate")? {
+        Some(date) => Some(date),
+        None => attribute(element, b"creationdate")?,
+    };
+    Ok(date
+        .and_then(|date| NaiveDateTime::parse_from_str(&date, DATE_FORMAT).ok())
+        .map(|date| date.and_utc().timestamp_millis()))
+}
+
+fn language_code(name: &str) -> String {
+    LANGUAGE_CODES
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map_or(name, |(_, code)| code)
+        .to_string()
+}
+
+/// Regional subtags are dropped, so `en-US` and `en-GB` both become `English`.
+/// The language part of a code such as `en-US` or `zh_Hans`.
+fn primary_subtag(code: &str) -> &str {
+    code.split(['-', '_']).next().unwrap_or(code)
+}
+
+fn language_name(code: &str) -> String {
+    LANGUAGE_CODES
+        .iter()
+        .find(|(_, known)| known.eq_ignore_ascii_case(primary_subtag(code)))
+        .map_or(code, |(name, _)| name)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_600_000_000_000;
+
+    fn pairs(units: &[TranslationUnit]) -> Vec<(&str, &str, &str, &str)> {
+        units
+            .iter()
+            .map(|unit| {
+                (
+                    unit.source_text.as_str(),
+                    unit.target_text.as_str(),
+                    unit.source_lang.as_str(),
+                    unit.target_lang.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    fn document(body: &str) -> String {
+        format!(
+            r#"<?xml version="1.0"?><tmx version="1.4"><header srclang="en"/><body>{body}</body></tmx>"#
+        )
+    }
+
+    #[test]
+    fn written_units_parse_back() {
+        let units = vec![TranslationUnit {
+            source_text: "Tom & Jerry <3".to_string(),
+            target_text: "猫和老鼠".to_string(),
+            source_lang: "English".to_string(),
+            target_lang: "中文".to_string(),
+            updated_at: 1_700_000_000_000,
+        }];
+        let parsed = parse(&write(&units).unwrap(), NOW).unwrap();
+        assert_eq!(pairs(&parsed), pairs(&units));
+        assert_eq!(parsed[0].updated_at, 1_700_000_000_000);
+    }
+
+    #[test]
+    fn inline_codes_are_left_out() {
+        let xml = document(
+            r#"<tu><tuv xml:lang="en"><seg>Click <bpt i="1">&lt;b&gt;</bpt>here<ept i="1">&lt;/b&gt;</ept> or <ph>{0}</ph>now</seg></tuv>
+               <tuv xml:lang="fr"><seg>Cliquez ici</seg></tuv></tu>"#,
+        );
+        let units = parse(&xml, NOW).unwrap();
+        assert_eq!(units[0].source_text, "Click here or now");
+        assert_eq!(units[0].updated_at, NOW);
+    }
+
+    #[test]
+    fn cdata_is_kept_verbatim() {
+        let xml = document(
+            r#"<tu><tuv xml:lang="en"><seg><![CDATA[a < b && c]]></seg></tuv>
+               <tuv xml:lang="de"><seg>a kleiner b</seg></tuv></tu>"#,
+        );
+        assert_eq!(parse(&xml, NOW).unwrap()[0].source_text, "a < b && c");
+    }
+
+    #[test]
+    fn plain_lang_attributes_and_regions_are_understood() {
+        let xml = document(
+            r#"<tu srclang="de" changedate="20240102T030405Z">
+                 <tuv lang="EN-US"><seg>good morning</seg></tuv>
+                 <tuv xml:lang="de-AT"><seg>guten Morgen</seg></tuv>
+                 <tuv xml:lang="xx"><seg>?</seg></tuv>
+               </tu>"#,
+        );
+        let units = parse(&xml, NOW).unwrap();
+        // `srclang="de"` names the `de-AT` variant by its primary subtag.
+        assert_eq!(
+            pairs(&units),
+            [
+                ("guten Morgen", "good morning", "Deutsch", "English"),
+                ("guten Morgen", "?", "Deutsch", "xx"),
+            ]
+        );
+        assert_eq!(units[0].updated_at, 1_704_164_645_000);
+    }
+
+    #[test]
+    fn an_exact_source_code_beats_a_regional_sibling() {
+        let xml = document(
+            r#"<tu srclang="en-GB">
+                 <tuv xml:lang="en-US"><seg>color</seg></tuv>
+                 <tuv xml:lang="en-GB"><seg>colour</seg></tuv>
+                 <tuv xml:lang="fr"><seg>couleur</seg></tuv>
+               </tu>"#,
+        );
+        // The other English variant is not paired with its sibling.
+        assert_eq!(
+            pairs(&parse(&xml, NOW).unwrap()),
+            [("colour", "couleur", "English", "Français")]
+        );
+    }
+
+    #[test]
+    fn without_a_matching_variant_the_first_one_is_the_source() {
+        let xml = document(
+            r#"<tu srclang="ja">
+                 <tuv xml:lang="ko"><seg>안녕</seg></tuv>
+                 <tuv xml:lang="zh-CN"><seg>你好</seg></tuv>
+               </tu>"#,
+        );
+        assert_eq!(
+            pairs(&parse(&xml, NOW).unwrap()),
+            [("안녕", "你好", "한국어", "中文")]
+        );
+    }
+
+    #[test]
+    fn documents_without_a_tmx_root_are_rejected() {
+        assert!(matches!(
+            parse("<xliff/>", NOW),
+            Err(TranslationMemoryError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,372 @@
+use super::tmx;
+use rusqlite::{params, Connection, Row};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Longer segments are only matched exactly; edit distance grows quadratically.
+const MAX_FUZZY_CHARS: usize = 500;
+/// Most recently used units compared per lookup.
+const MAX_CANDIDATES: u32 = 500;
+/// Similarities are compared in ten-thousandths, so a threshold is never missed by rounding.
+const SIMILARITY_SCALE: usize = 10_000;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS translation_units (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source_text TEXT NOT NULL,
+    target_text TEXT NOT NULL,
+    source_lang TEXT NOT NULL,
+    target_lang TEXT NOT NULL,
+    char_count INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    UNIQUE (source_text, source_lang, target_lang)
+);
+CREATE INDEX IF NOT EXISTS translation_units_lookup
+    ON translation_units (source_lang, target_lang, char_count);
+";
+
+#[derive(Debug, thiserror::Error)]
+pub enum TranslationMemoryError {
+    #[error("translation memory database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("invalid tmx: {0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("invalid tmx: {0}")]
+    Invalid(&'static str),
+}
+
+/// How previous translations are reused as reference examples.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TranslationMemorySettings {
+    pub enabled: bool,
+    /// From 0 to 1; `1 - edit distance / length of the longer text`.
+    pub min_similarity: f32,
+    /// Reference examples passed to the model per segment.
+    pub max_matches: usize,
+}
+
+impl Default for TranslationMemorySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_similarity: 0.85,
+            max_matches: 3,
+        }
+    }
+}
+
+/// A source segment and its translation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranslationUnit {
+    pub source_text: String,
+    pub target_text: String,
+    pub source_lang: String,
+    pub target_lang: String,
+    /// Unix time in milliseconds.
+    pub updated_at: i64,
+}
+
+/// A stored translation whose source resembles the segment being translated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryMatch {
+    pub source_text: String,
+    pub target_text: String,
+    pub similarity: f32,
+}
+
+/// Segment translations in SQLite, looked up by edit-distance similarity.
+#[derive(Clone)]
+pub struct TranslationMemoryStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl TranslationMemoryStore {
+    /// Opens the database at `path`, or an in-memory one if that fails.
+    pub fn open(path: &Path) -> Self {
+        let conn = Self::open_file(path).unwrap_or_else(|err| {
+            warn!(%err, path = %path.display(), "translation memory unavailable, keeping segments in memory");
+            let conn = Connection::open_in_memory().expect("in-memory sqlite");
+            conn.execute_batch(SCHEMA).expect("translation memory schema");
+            conn
+        });
+        Self {
+            conn: Arc::new(Mutex::new(conn)),
+        }
+    }
+
+    fn open_file(path: &Path) -> Result<Connection, TranslationMemoryError> {
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(conn)
+    }
+
+    /// Stores a translated segment, replacing an earlier translation of the same source.
+    pub fn record(&self, unit: &TranslationUnit) -> Result<(), TranslationMemoryError> {
+        let conn = self.conn.lock().expect("translation memory lock");
+        upsert(&conn, unit)?;
+        Ok(())
+    }
+
+    /// Up to `settings.max_matches` units at least `settings.min_similarity`
+    /// alike to `text`, best first.
+    pub fn lookup(
+        &self,
+        text: &str,
+        source_lang: &str,
+        target_lang: &str,
+        settings: &TranslationMemorySettings,
+    ) -> Result<Vec<MemoryMatch>, TranslationMemoryError> {
+        let text = normalize(text);
+        let chars: Vec<char> = text.chars().collect();
+        if chars.is_empty() || settings.max_matches == 0 {
+            return Ok(Vec::new());
+        }
+        let min_similarity = settings.min_similarity.clamp(0.0, 1.0);
+        let (min_len, max_len) = if chars.len() > MAX_FUZZY_CHARS {
+            (chars.len(), chars.len())
+        } else {
+            let min = scaled(min_similarity);
+            (
+                (chars.len() * min).div_ceil(SIMILARITY_SCALE),
+                (chars.len() * SIMILARITY_SCALE)
+                    .checked_div(min)
+                    .unwrap_or(i64::MAX as usize),
+            )
+        };
+
+        let conn = self.conn.lock().expect("translation memory lock");
+        let mut statement = conn.prepare(
+            "SELECT source_text, target_text FROM translation_units \
+             WHERE source_lang = ?1 AND target_lang = ?2 AND char_count BETWEEN ?3 AND ?4 \
+             ORDER BY updated_at DESC LIMIT ?5",
+        )?;
+        let rows = statement.query_map(
+            params![source_lang, target_lang, min_len, max_len, MAX_CANDIDATES],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )?;
+
+        let mut matches = Vec::new();
+        for row in rows {
+            let (source_text, target_text) = row?;
+            let similarity = if source_text == text {
+                1.0
+            } else if chars.len() > MAX_FUZZY_CHARS {
+                continue;
+            } else {
+                let candidate: Vec<char> = source_text.chars().collect();
+                match similarity(&chars, &candidate, min_similarity) {
+                    Some(similarity) => similarity,
+                    None => continue,
+                }
+            };
+            matches.push(MemoryMatch {
+                source_text,
+                target_text,
+                similarity,
+            });
+        }
+        matches.sort_by(|left, right| right.similarity.total_cmp(&left.similarity));
+        matches.truncate(settings.max_matches);
+        Ok(matches)
+    }
+
+    pub fn count(&self) -> Result<u64, TranslationMemoryError> {
+        let conn = self.conn.lock().expect("translation memory lock");
+        Ok(
+            conn.query_row("SELECT COUNT(*) FROM translation_units", [], |row| {
+                row.get(0)
+            })?,
+        )
+    }
+
+    pub fn clear(&self) -> Result<usize, TranslationMemoryError> {
+        let conn = self.conn.lock().expect("translation memory lock");
+        Ok(conn.execute("DELETE FROM translation_units", [])?)
+    }
+
+    /// Adds every language pair found in a TMX document and returns how many
+    /// units were stored. Units without a date are stamped `now`.
+    pub fn import_tmx(&self, xml: &str, now: i64) -> Result<usize, TranslationMemoryError> {
+        let units = tmx::parse(xml, now)?;
+        let mut conn = self.conn.lock().expect("translation memory lock");
+        let transaction = conn.transaction()?;
+        let mut imported = 0;
+        for unit in &units {
+            imported += upsert(&transaction, unit)?;
+        }
+        transaction.commit()?;
+        Ok(imported)
+    }
+
+    /// The whole memory as a TMX 1.4 document, oldest units first.
+    pub fn export_tmx(&self) -> Result<String, TranslationMemoryError> {
+        let conn = self.conn.lock().expect("translation memory lock");
+        let mut statement = conn.prepare(
+            "SELECT source_text, target_text, source_lang, target_lang, updated_at \
+             FROM translation_units ORDER BY id ASC",
+        )?;
+        let units = statement
+            .query_map([], read_unit)?
+            .collect::<Result<Vec<_>, _>>()?;
+        tmx::write(&units)
+    }
+}
+
+fn upsert(conn: &Connection, unit: &TranslationUnit) -> rusqlite::Result<usize> {
+    let source_text = normalize(&unit.source_text);
+    let target_text = unit.target_text.trim();
+    if source_text.is_empty() || target_text.is_empty() {
+        return Ok(0);
+    }
+    conn.execute(
+        "INSERT INTO translation_units (source_text, target_text, source_lang, target_lang, \
+         char_count, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6) \
+         ON CONFLICT (source_text, source_lang, target_lang) \
+         DO UPDATE SET target_text = excluded.target_text, updated_at = excluded.updated_at",
+        params![
+            source_text,
+            target_text,
+            unit.source_lang,
+            unit.target_lang,
+            source_text.chars().count(),
+            unit.updated_at,
+        ],
+    )
+}
+
+fn read_unit(row: &Row<'_>) -> rusqlite::Result<TranslationUnit> {
+    Ok(TranslationUnit {
+        source_text: row.get(0)?,
+        target_text: row.get(1)?,
+        source_lang: row.get(2)?,
+        target_lang: row.get(3)?,
+        updated_at: row.get(4)?,
+    })
+}
+
+/// Runs of whitespace do not change a segment.
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Levenshtein similarity over characters, or `None` once it cannot reach `min`.
+fn similarity(left: &[char], right: &[char], min: f32) -> Option<f32> {
+    let longest = left.len().max(right.len());
+    if longest == 0 {
+        return Some(1.0);
+    }
+    let max_distance = (SIMILARITY_SCALE - scaled(min)) * longest / SIMILARITY_SCALE;
+    if left.len().abs_diff(right.len()) > max_distance {
+        return None;
+    }
+
+    let mut previous: Vec<usize> = (0..=right.len()).collect();
+    let mut current = vec![0; right.len() + 1];
+    for (i, left_char) in left.iter().enumerate() {
+        current[0] = i + 1;
+        let mut row_min = current[0];
+        for (j, right_char) in right.iter().enumerate() {
+            let substitution = previous[j] + usize::from(left_char != right_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+            row_min = row_min.min(current[j + 1]);
+        }
+        if row_min > max_distance {
+            return None;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    let distance = previous[right.len()];
+    (distance <= max_distance).then(|| 1.0 - distance as f32 / longest as f32)
+}
+
+/// `similarity` in ten-thousandths.
+fn scaled(similarity: f32) -> usize {
+    (similarity.clamp(0.0, 1.0) * SIMILARITY_SCALE as f32).round() as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(left: &str, right: &str, min: f32) -> Option<f32> {
+        let left: Vec<char> = left.chars().collect();
+        let right: Vec<char> = right.chars().collect();
+        similarity(&left, &right, min)
+    }
+
+    #[test]
+    fn similarity_is_one_minus_the_relative_edit_distance() {
+        assert_eq!(score("abcdefghij", "abcdefghij", 0.5), Some(1.0));
+        assert_eq!(score("abcdefghij", "abcdefghiX", 0.5), Some(0.9));
+        assert_eq!(
+            score("今天天气很好", "今天天气不好", 0.5),
+            Some(1.0 - 1.0 / 6.0)
+        );
+        assert_eq!(score("", "", 0.9), Some(1.0));
+    }
+
+    #[test]
+    fn similarity_gives_up_below_the_minimum() {
+        assert_eq!(score("abcdefghij", "abcdeXXXXX", 0.6), None);
+        assert_eq!(score("abcdefghij", "abcdeXXXXX", 0.5), Some(0.5));
+        // Rejected by length alone.
+        assert_eq!(score("short", "a much longer sentence", 0.7), None);
+    }
+
+    #[test]
+    fn a_candidate_exactly_at_the_minimum_is_kept() {
+        let similarity = score("abcdefghijklmnopqrst", "abcdefghijklmnopqXYZ", 0.85);
+        assert!((similarity.unwrap() - 0.85).abs() < 1e-6);
+        assert_eq!(
+            score("abcdefghijklmnopqrst", "abcdefghijklmnoWXYZ", 0.85),
+            None
+        );
+    }
+
+    #[test]
+    fn lookups_keep_candidates_at_either_length_bound() {
+        let store = TranslationMemoryStore::open(Path::new(":memory:"));
+        for source_text in ["abcdefghijklmnopq", "abcdefghijklmnopqrst"] {
+            store
+                .record(&TranslationUnit {
+                    source_text: source_text.to_string(),
+                    target_text: source_text.to_uppercase(),
+                    source_lang: "English".to_string(),
+                    target_lang: "中文".to_string(),
+                    updated_at: 0,
+                })
+                .unwrap();
+        }
+        let settings = TranslationMemorySettings::default();
+        let found = |text: &str| -> Vec<String> {
+            store
+                .lookup(text, "English", "中文", &settings)
+                .unwrap()
+                .into_iter()
+                .map(|found| found.source_text)
+                .collect()
+        };
+
+        // 17 of 20 characters: exactly 85% alike in both directions.
+        assert_eq!(
+            found("abcdefghijklmnopqrst"),
+            ["abcdefghijklmnopqrst", "abcdefghijklmnopq"]
+        );
+        assert_eq!(
+            found("abcdefghijklmnopq"),
+            ["abcdefghijklmnopq", "abcdefghijklmnopqrst"]
+        );
+    }
+
+    #[test]
+    fn normalize_collapses_whitespace() {
+        assert_eq!(normalize("  one \n two\tthree "), "one two three");
+    }
+}
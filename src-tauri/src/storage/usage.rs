@@ -0,0 +1,295 @@
+use super::{quarantine, write_atomic};
+use crate::api_client::TokenUsage;
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Daily counters older than this are dropped; monthly ones are kept.
+const DAILY_RETENTION_DAYS: i64 = 90;
+
+/// A calendar period that usage is counted and budgeted over, in local time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+    Daily,
+    Monthly,
+}
+
+impl Period {
+    fn key(self, now: &DateTime<Local>) -> String {
+        match self {
+            Self::Daily => now.format("%Y-%m-%d").to_string(),
+            Self::Monthly => now.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+/// Price of a model in the provider's billing currency, per million tokens.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub model: String,
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl ModelPrice {
+    fn cost(&self, usage: &TokenUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input_per_million
+            + usage.completion_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+/// Cost of `usage` on `model`; zero for models missing from `prices`.
+pub fn estimate_cost(prices: &[ModelPrice], model: &str, usage: &TokenUsage) -> f64 {
+    prices
+        .iter()
+        .find(|price| price.model == model)
+        .map_or(0.0, |price| price.cost(usage))
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageTotals {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Zero for models missing from the price table.
+    pub estimated_cost: f64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, usage: &TokenUsage, cost: f64) {
+        self.requests += 1;
+        self.prompt_tokens += usage.prompt_tokens;
+        self.completion_tokens += usage.completion_tokens;
+        self.estimated_cost += cost;
+    }
+}
+
+/// Usage within one day or month, broken down by model and by key name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsagePeriod {
+    pub total: UsageTotals,
+    pub by_model: BTreeMap<String, UsageTotals>,
+    pub by_key: BTreeMap<String, UsageTotals>,
+}
+
+impl UsagePeriod {
+    fn add(&mut self, model: &str, key: &str, usage: &TokenUsage, cost: f64) {
+        self.total.add(usage, cost);
+        self.by_model
+            .entry(model.to_string())
+            .or_default()
+            .add(usage, cost);
+        self.by_key
+            .entry(key.to_string())
+            .or_default()
+            .add(usage, cost);
+    }
+}
+
+/// Counters keyed by local date (`2024-05-01`) and month (`2024-05`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageStats {
+    pub daily: BTreeMap<String, UsagePeriod>,
+    pub monthly: BTreeMap<String, UsagePeriod>,
+}
+
+/// Token counters, persisted as JSON after recorded requests.
+#[derive(Clone)]
+pub struct UsageStore {
+    path: PathBuf,
+    stats: Arc<Mutex<UsageStats>>,
+    /// Set while a write is queued; requests recorded meanwhile ride along.
+    write_queued: Arc<Mutex<bool>>,
+    /// Held during a write, so an older snapshot never lands after a newer one.
+    writing: Arc<Mutex<()>>,
+}
+
+impl UsageStore {
+    pub fn open(path: PathBuf) -> Self {
+        let stats = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|err| {
+                warn!(?err, path = %path.display(), "unreadable usage statistics, starting fresh");
+                if let Err(err) = quarantine(&path) {
+                    warn!(?err, "failed to move unreadable usage statistics aside");
+                }
+                UsageStats::default()
+            }),
+            Err(_) => UsageStats::default(),
+        };
+        Self {
+            path,
+            stats: Arc::new(Mutex::new(stats)),
+            write_queued: Arc::default(),
+            writing: Arc::default(),
+        }
+    }
+
+    pub fn stats(&self) -> UsageStats {
+        self.stats.lock().expect("usage lock").clone()
+    }
+
+    /// The current day's or month's key and counters.
+    pub fn current(&self, period: Period) -> (String, UsageTotals) {
+        let key = period.key(&Local::now());
+        let stats = self.stats.lock().expect("usage lock");
+        let counters = match period {
+            Period::Daily => &stats.daily,
+            Period::Monthly => &stats.monthly,
+        };
+        let totals = counters
+            .get(&key)
+            .map(|period| period.total.clone())
+            .unwrap_or_default();
+        (key, totals)
+    }
+
+    /// Adds one request's tokens to today's and this month's counters.
+    ///
+    /// The file is written off the async runtime; requests recorded while a
+    /// write is queued are saved by that same write.
+    pub fn record(&self, model: &str, key: &str, usage: &TokenUsage, prices: &[ModelPrice]) {
+        self.add(Local::now(), model, key, usage, prices);
+
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            self.persist();
+            return;
+        };
+        if std::mem::replace(&mut *self.write_queued.lock().expect("usage lock"), true) {
+            return;
+        }
+        let store = self.clone();
+        runtime.spawn_blocking(move || {
+            *store.write_queued.lock().expect("usage lock") = false;
+            store.persist();
+        });
+    }
+
+    fn add(
+        &self,
+        now: DateTime<Local>,
+        model: &str,
+        key: &str,
+        usage: &TokenUsage,
+        prices: &[ModelPrice],
+    ) {
+        let cost = estimate_cost(prices, model, usage);
+        let cutoff = Period::Daily.key(&(now - Duration::days(DAILY_RETENTION_DAYS)));
+
+        let mut stats = self.stats.lock().expect("usage lock");
+        stats
+            .daily
+            .entry(Period::Daily.key(&now))
+            .or_default()
+            .add(model, key, usage, cost);
+        stats
+            .monthly
+            .entry(Period::Monthly.key(&now))
+            .or_default()
+            .add(model, key, usage, cost);
+        stats.daily.retain(|day, _| *day > cutoff);
+    }
+
+    fn persist(&self) {
+        let _writing = self.writing.lock().expect("usage write lock");
+        let bytes = serde_json::to_vec_pretty(&*self.stats.lock().expect("usage lock"));
+        let result = bytes
+            .map_err(std::io::Error::from)
+            .and_then(|bytes| write_atomic(&self.path, &bytes));
+        if let Err(err) = result {
+            warn!(%err, "failed to persist usage statistics");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn tokens(prompt_tokens: u64, completion_tokens: u64) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+
+    fn at(year: i32, month: u32, day: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(year, month, day, 12, 0, 0)
+            .single()
+            .expect("local time")
+    }
+
+    fn store() -> (tempfile::TempDir, UsageStore) {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let store = UsageStore::open(dir.path().join("usage.json"));
+        (dir, store)
+    }
+
+    #[test]
+    fn days_and_months_roll_over_separately() {
+        let (_dir, store) = store();
+        store.add(at(2024, 1, 31), "m", "k", &tokens(10, 5), &[]);
+        store.add(at(2024, 1, 31), "m", "k", &tokens(1, 1), &[]);
+        store.add(at(2024, 2, 1), "m", "k", &tokens(100, 50), &[]);
+
+        let stats = store.stats();
+        assert_eq!(stats.daily["2024-01-31"].total.requests, 2);
+        assert_eq!(stats.daily["2024-01-31"].total.prompt_tokens, 11);
+        assert_eq!(stats.daily["2024-02-01"].total.completion_tokens, 50);
+        assert_eq!(stats.monthly["2024-01"].total.requests, 2);
+        assert_eq!(stats.monthly["2024-02"].total.prompt_tokens, 100);
+    }
+
+    #[test]
+    fn old_days_are_dropped_but_months_are_kept() {
+        let (_dir, store) = store();
+        store.add(at(2024, 1, 1), "m", "k", &tokens(1, 1), &[]);
+        store.add(at(2024, 6, 1), "m", "k", &tokens(1, 1), &[]);
+
+        let stats = store.stats();
+        assert_eq!(stats.daily.keys().collect::<Vec<_>>(), ["2024-06-01"]);
+        assert_eq!(stats.monthly.len(), 2);
+    }
+
+    #[test]
+    fn costs_use_the_model_price_per_million_tokens() {
+        let (_dir, store) = store();
+        let prices = [ModelPrice {
+            model: "priced".to_string(),
+            input_per_million: 2.0,
+            output_per_million: 8.0,
+        }];
+        store.add(
+            at(2024, 3, 1),
+            "priced",
+            "a",
+            &tokens(500_000, 250_000),
+            &prices,
+        );
+        store.add(at(2024, 3, 1), "free", "b", &tokens(1_000, 1_000), &prices);
+
+        let month = &store.stats().monthly["2024-03"];
+        assert!((month.by_model["priced"].estimated_cost - 3.0).abs() < 1e-9);
+        assert_eq!(month.by_model["free"].estimated_cost, 0.0);
+        assert_eq!(month.by_key["b"].requests, 1);
+        assert!((month.total.estimated_cost - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn recorded_usage_survives_a_reopen() {
+        let (dir, store) = store();
+        store.record("m", "k", &tokens(3, 4), &[]);
+        let reopened = UsageStore::open(dir.path().join("usage.json"));
+        assert_eq!(reopened.stats(), store.stats());
+    }
+}
@@ -0,0 +1,417 @@
+use super::{AppSettings, BudgetAction, AUTO_LANG};
+use crate::api_client::{
+    check_network, HttpConfigError, ModelInfo, OPENAI_PROVIDER, SILICONFLOW_PROVIDER,
+};
+use serde::Serialize;
+use std::collections::HashSet;
+
+const MAX_SELECTION_LEN: usize = 20_000;
+const MAX_DEBOUNCE_MS: u64 = 5_000;
+const SEGMENT_BUDGET_RANGE: std::ops::RangeInclusive<usize> = 100..=8_000;
+const SEGMENT_CONCURRENCY_RANGE: std::ops::RangeInclusive<usize> = 1..=8;
+const MAX_SEGMENT_RETRIES: u32 = 5;
+const RETRY_ATTEMPTS_RANGE: std::ops::RangeInclusive<u32> = 1..=10;
+const MAX_RETRY_BASE_DELAY_MS: u64 = 10_000;
+const MAX_RETRY_DEADLINE_MS: u64 = 300_000;
+const TIMEOUT_MS_RANGE: std::ops::RangeInclusive<u64> = 1_000..=300_000;
+const MAX_CACHE_MEMORY_ENTRIES: usize = 10_000;
+const MAX_CACHE_DISK_BYTES: u64 = 1024 * 1024 * 1024;
+const MAX_MEMORY_MATCHES: usize = 10;
+const MAX_SEMANTIC_EXAMPLES: usize = 10;
+
+/// One invalid setting, addressed by its field path (e.g. `prompt_profiles[1].user_template`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// Every problem found in a settings object, so the UI can mark all bad fields at once.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, thiserror::Error)]
+#[error("invalid settings: {}", summary(.errors))]
+pub struct SettingsValidationError {
+    pub errors: Vec<FieldError>,
+}
+
+fn summary(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|error| format!("{}: {}", error.field, error.message))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[derive(Default)]
+struct Collector {
+    errors: Vec<FieldError>,
+}
+
+impl Collector {
+    fn push(&mut self, field: impl Into<String>, code: &'static str, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: field.into(),
+            code,
+            message: message.into(),
+        });
+    }
+
+    fn require(&mut self, field: &str, value: &str) {
+        if value.trim().is_empty() {
+            self.push(field, "required", "must not be empty");
+        }
+    }
+}
+
+impl AppSettings {
+    /// Checks every field, and `model` against the provider's `available`
+    /// models when that list is known.
+    pub fn validate(&self, available: Option<&[ModelInfo]>) -> Result<(), SettingsValidationError> {
+        let mut errors = Collector::default();
+
+        if let Some(available) = available {
+            let model = self.model.trim();
+            if !model.is_empty() && !available.iter().any(|info| info.id == model) {
+                errors.push(
+                    "model",
+                    "unknown_model",
+                    format!("{model} is not offered by {}", self.provider),
+                );
+            }
+        }
+
+        errors.require("model", &self.model);
+        errors.require("source_lang", &self.source_lang);
+        errors.require("target_lang", &self.target_lang);
+        errors.require("secondary_target_lang", &self.secondary_target_lang);
+        for (field, value) in [
+            ("target_lang", &self.target_lang),
+            ("secondary_target_lang", &self.secondary_target_lang),
+        ] {
+            if value == AUTO_LANG {
+                errors.push(
+                    field,
+                    "invalid_language",
+                    "target language cannot be automatic",
+                );
+            }
+        }
+
+        if self.selection_min_len == 0 {
+            errors.push("selection_min_len", "out_of_range", "must be at least 1");
+        }
+        if self.selection_max_len > MAX_SELECTION_LEN {
+            errors.push(
+                "selection_max_len",
+                "out_of_range",
+                format!("must be at most {MAX_SELECTION_LEN}"),
+            );
+        }
+        if self.selection_min_len > self.selection_max_len {
+            errors.push(
+                "selection_min_len",
+                "min_exceeds_max",
+                "must not exceed selection_max_len",
+            );
+        }
+        if self.debounce_ms > MAX_DEBOUNCE_MS {
+            errors.push(
+                "debounce_ms",
+                "out_of_range",
+                format!("must be at most {MAX_DEBOUNCE_MS} ms"),
+            );
+        }
+
+        if !SEGMENT_BUDGET_RANGE.contains(&self.segment_token_budget) {
+            errors.push(
+                "segment_token_budget",
+                "out_of_range",
+                format!(
+                    "must be between {} and {}",
+                    SEGMENT_BUDGET_RANGE.start(),
+                    SEGMENT_BUDGET_RANGE.end()
+                ),
+            );
+        }
+        if !SEGMENT_CONCURRENCY_RANGE.contains(&self.segment_concurrency) {
+            errors.push(
+                "segment_concurrency",
+                "out_of_range",
+                format!(
+                    "must be between {} and {}",
+                    SEGMENT_CONCURRENCY_RANGE.start(),
+                    SEGMENT_CONCURRENCY_RANGE.end()
+                ),
+            );
+        }
+        if self.segment_max_retries > MAX_SEGMENT_RETRIES {
+            errors.push(
+                "segment_max_retries",
+                "out_of_range",
+                format!("must be at most {MAX_SEGMENT_RETRIES}"),
+            );
+        }
+
+        self.validate_retry(&mut errors);
+        self.validate_network(&mut errors);
+        self.validate_providers(&mut errors);
+        self.validate_prompts(&mut errors);
+        self.validate_prices(&mut errors);
+        self.validate_budget(&mut errors);
+        if self.cache.memory_entries > MAX_CACHE_MEMORY_ENTRIES {
+            errors.push(
+                "cache.memory_entries",
+                "out_of_range",
+                format!("must be at most {MAX_CACHE_MEMORY_ENTRIES}"),
+            );
+        }
+        if self.cache.max_disk_bytes > MAX_CACHE_DISK_BYTES {
+            errors.push(
+                "cache.max_disk_bytes",
+                "out_of_range",
+                format!("must be at most {MAX_CACHE_DISK_BYTES} bytes"),
+            );
+        }
+        let memory = &self.translation_memory;
+        if !(memory.min_similarity > 0.0 && memory.min_similarity <= 1.0) {
+            errors.push(
+                "translation_memory.min_similarity",
+                "out_of_range",
+                "must be above 0 and at most 1",
+            );
+        }
+        if memory.max_matches > MAX_MEMORY_MATCHES {
+            errors.push(
+                "translation_memory.max_matches",
+                "out_of_range",
+                format!("must be at most {MAX_MEMORY_MATCHES}"),
+            );
+        }
+        let semantic = &self.semantic_retrieval;
+        if semantic.enabled {
+            errors.require("semantic_retrieval.model", &semantic.model);
+        }
+        if semantic.top_k > MAX_SEMANTIC_EXAMPLES {
+            errors.push(
+                "semantic_retrieval.top_k",
+                "out_of_range",
+                format!("must be at most {MAX_SEMANTIC_EXAMPLES}"),
+            );
+        }
+        if !(0.0..=1.0).contains(&semantic.min_score) {
+            errors.push(
+                "semantic_retrieval.min_score",
+                "out_of_range",
+                "must be between 0 and 1",
+            );
+        }
+
+        if errors.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(SettingsValidationError {
+                errors: errors.errors,
+            })
+        }
+    }
+
+    fn validate_retry(&self, errors: &mut Collector) {
+        let retry = &self.retry;
+        if !RETRY_ATTEMPTS_RANGE.contains(&retry.max_attempts) {
+            errors.push(
+                "retry.max_attempts",
+                "out_of_range",
+                format!(
+                    "must be between {} and {}",
+                    RETRY_ATTEMPTS_RANGE.start(),
+                    RETRY_ATTEMPTS_RANGE.end()
+                ),
+            );
+        }
+        if retry.base_delay_ms > MAX_RETRY_BASE_DELAY_MS {
+            errors.push(
+                "retry.base_delay_ms",
+                "out_of_range",
+                format!("must be at most {MAX_RETRY_BASE_DELAY_MS} ms"),
+            );
+        }
+        if !(0.0..=1.0).contains(&retry.jitter) {
+            errors.push("retry.jitter", "out_of_range", "must be between 0 and 1");
+        }
+        if retry.deadline_ms > MAX_RETRY_DEADLINE_MS {
+            errors.push(
+                "retry.deadline_ms",
+                "out_of_range",
+                format!("must be at most {MAX_RETRY_DEADLINE_MS} ms"),
+            );
+        }
+    }
+
+    fn validate_network(&self, errors: &mut Collector) {
+        let network = &self.network;
+        if network.base_url().is_some_and(|url| !is_http_url(url)) {
+            errors.push("network.base_url", "invalid_url", "must be an http(s) URL");
+        }
+        for (field, value) in [
+            ("network.connect_timeout_ms", network.connect_timeout_ms),
+            ("network.request_timeout_ms", network.request_timeout_ms),
+        ] {
+            if !TIMEOUT_MS_RANGE.contains(&value) {
+                errors.push(
+                    field,
+                    "out_of_range",
+                    format!(
+                        "must be between {} and {} ms",
+                        TIMEOUT_MS_RANGE.start(),
+                        TIMEOUT_MS_RANGE.end()
+                    ),
+                );
+            }
+        }
+
+        let Err(err) = check_network(network) else {
+            return;
+        };
+        let (field, code) = match &err {
+            HttpConfigError::Proxy(_) => ("network.proxy".to_string(), "invalid_proxy"),
+            HttpConfigError::CaBundlePath(_)
+            | HttpConfigError::CaBundleIo(_)
+            | HttpConfigError::CaBundle(_) => {
+                ("network.ca_bundle".to_string(), "invalid_ca_bundle")
+            }
+            HttpConfigError::Header { index, .. } => {
+                (format!("network.headers[{index}]"), "invalid_header")
+            }
+            HttpConfigError::Client(_) => ("network".to_string(), "invalid_network"),
+        };
+        errors.push(field, code, err.to_string());
+    }
+
+    fn validate_prices(&self, errors: &mut Collector) {
+        let mut models: HashSet<&str> = HashSet::new();
+        for (index, price) in self.model_prices.iter().enumerate() {
+            let field = format!("model_prices[{index}]");
+            if price.model.trim().is_empty() {
+                errors.push(format!("{field}.model"), "required", "must not be empty");
+            } else if !models.insert(&price.model) {
+                errors.push(
+                    format!("{field}.model"),
+                    "duplicate",
+                    "model already has a price",
+                );
+            }
+            for (name, value) in [
+                ("input_per_million", price.input_per_million),
+                ("output_per_million", price.output_per_million),
+            ] {
+                if !value.is_finite() || value < 0.0 {
+                    errors.push(
+                        format!("{field}.{name}"),
+                        "out_of_range",
+                        "must be a non-negative number",
+                    );
+                }
+            }
+        }
+    }
+
+    fn validate_budget(&self, errors: &mut Collector) {
+        let budget = &self.budget;
+        for (field, limit) in [
+            (
+                "budget.daily_tokens",
+                budget.daily_tokens.map(|limit| limit as f64),
+            ),
+            (
+                "budget.monthly_tokens",
+                budget.monthly_tokens.map(|limit| limit as f64),
+            ),
+            ("budget.daily_cost", budget.daily_cost),
+            ("budget.monthly_cost", budget.monthly_cost),
+        ] {
+            if limit.is_some_and(|limit| !limit.is_finite() || limit <= 0.0) {
+                errors.push(field, "out_of_range", "must be greater than 0");
+            }
+        }
+        for (index, threshold) in budget.warn_at.iter().enumerate() {
+            if !(*threshold > 0.0 && *threshold < 1.0) {
+                errors.push(
+                    format!("budget.warn_at[{index}]"),
+                    "out_of_range",
+                    "must be between 0 and 1",
+                );
+            }
+        }
+        if budget.on_exceeded == BudgetAction::Downgrade && budget.fallback_model().is_none() {
+            errors.push(
+                "budget.fallback_model",
+                "required",
+                "required when on_exceeded is downgrade",
+            );
+        }
+    }
+
+    fn validate_providers(&self, errors: &mut Collector) {
+        let mut names: HashSet<&str> = HashSet::new();
+        for (index, provider) in self.custom_providers.iter().enumerate() {
+            let field = format!("custom_providers[{index}]");
+            if provider.name.trim().is_empty() {
+                errors.push(format!("{field}.name"), "required", "must not be empty");
+            } else if !names.insert(&provider.name) {
+                errors.push(
+                    format!("{field}.name"),
+                    "duplicate",
+                    "provider name already used",
+                );
+            }
+            if !is_http_url(&provider.endpoint) {
+                errors.push(
+                    format!("{field}.endpoint"),
+                    "invalid_url",
+                    "must be an http(s) URL",
+                );
+            }
+        }
+
+        let known = [SILICONFLOW_PROVIDER, OPENAI_PROVIDER].contains(&self.provider.as_str())
+            || names.contains(self.provider.as_str());
+        if !known {
+            errors.push("provider", "unknown_provider", "no provider with this name");
+        }
+    }
+
+    fn validate_prompts(&self, errors: &mut Collector) {
+        let mut names: HashSet<&str> = HashSet::new();
+        for (index, profile) in self.prompt_profiles.iter().enumerate() {
+            let field = format!("prompt_profiles[{index}]");
+            if profile.name.trim().is_empty() {
+                errors.push(format!("{field}.name"), "required", "must not be empty");
+            } else if !names.insert(&profile.name) {
+                errors.push(
+                    format!("{field}.name"),
+                    "duplicate",
+                    "profile name already used",
+                );
+            }
+            if let Err(err) = profile.validate() {
+                errors.push(
+                    format!("{field}.{}", err.field()),
+                    err.code(),
+                    err.to_string(),
+                );
+            }
+        }
+
+        if !names.contains(self.active_prompt_profile.as_str()) {
+            errors.push(
+                "active_prompt_profile",
+                "unknown_profile",
+                "no prompt profile with this name",
+            );
+        }
+    }
+}
+
+fn is_http_url(url: &str) -> bool {
+    url.starts_with("https://") || url.starts_with("http://")
+}
@@ -0,0 +1,372 @@
+use super::anki;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+const MAX_WORD_CHARS: usize = 64;
+const INITIAL_EASE: f64 = 2.5;
+const MIN_EASE: f64 = 1.3;
+pub const DEFAULT_DECK_NAME: &str = "Silicon Translater";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS vocabulary (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    word TEXT NOT NULL,
+    translation TEXT NOT NULL,
+    context TEXT NOT NULL,
+    source_lang TEXT NOT NULL,
+    target_lang TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    ease REAL NOT NULL,
+    interval_days INTEGER NOT NULL,
+    repetitions INTEGER NOT NULL,
+    lapses INTEGER NOT NULL,
+    due_at INTEGER NOT NULL,
+    UNIQUE (word, source_lang, target_lang)
+);
+CREATE INDEX IF NOT EXISTS vocabulary_due_at ON vocabulary (due_at);
+";
+
+const COLUMNS: &str = "id, word, translation, context, source_lang, target_lang, created_at, \
+    ease, interval_days, repetitions, lapses, due_at";
+
+#[derive(Debug, thiserror::Error)]
+pub enum VocabularyError {
+    #[error("vocabulary database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("cannot write anki package: {0}")]
+    Package(#[from] zip::result::ZipError),
+    #[error("{0}")]
+    Invalid(&'static str),
+    #[error("vocabulary entry {0} not found")]
+    NotFound(i64),
+}
+
+/// A word or phrase to be starred, usually taken from a `TranslationResponse`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewVocabularyEntry {
+    pub word: String,
+    pub translation: String,
+    /// The sentence the word was found in.
+    #[serde(default)]
+    pub context: String,
+    pub source_lang: String,
+    pub target_lang: String,
+}
+
+/// A starred word and its SM-2 review state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VocabularyEntry {
+    pub id: i64,
+    pub word: String,
+    pub translation: String,
+    pub context: String,
+    pub source_lang: String,
+    pub target_lang: String,
+    /// Unix time in milliseconds, like `due_at`.
+    pub created_at: i64,
+    pub ease: f64,
+    pub interval_days: u32,
+    /// Successful reviews in a row.
+    pub repetitions: u32,
+    pub lapses: u32,
+    pub due_at: i64,
+}
+
+impl VocabularyEntry {
+    /// Applies an SM-2 review graded from 0 (forgot) to 5 (perfect recall).
+    fn review(&mut self, grade: u8, now: i64) {
+        let grade = f64::from(grade);
+        if grade < 3.0 {
+            self.repetitions = 0;
+            self.interval_days = 1;
+            self.lapses += 1;
+        } else {
+            self.repetitions += 1;
+            self.interval_days = match self.repetitions {
+                1 => 1,
+                2 => 6,
+                _ => (f64::from(self.interval_days) * self.ease).round() as u32,
+            };
+        }
+        let miss = 5.0 - grade;
+        self.ease = (self.ease + 0.1 - miss * (0.08 + miss * 0.02)).max(MIN_EASE);
+        self.due_at = now + i64::from(self.interval_days) * DAY_MS;
+    }
+}
+
+/// Starred words in SQLite, next to the translation history.
+#[derive(Clone)]
+pub struct VocabularyStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl VocabularyStore {
+    /// Opens the database at `path`, or an in-memory one if that fails.
+    pub fn open(path: &Path) -> Self {
+        let conn = Self::open_file(path).unwrap_or_else(|err| {
+            warn!(%err, path = %path.display(), "vocabulary database unavailable, keeping words in memory");
+            let conn = Connection::open_in_memory().expect("in-memory sqlite");
+            conn.execute_batch(SCHEMA).expect("vocabulary schema");
+            conn
+        });
+        Self {
+            conn: Arc::new(Mutex::new(conn)),
+        }
+    }
+
+    fn open_file(path: &Path) -> Result<Connection, VocabularyError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(conn)
+    }
+
+    /// Stars a word, or updates its translation and context if it is already starred.
+    ///
+    /// Only single words and short phrases are accepted. A new word is due for
+    /// review right away; a re-starred one keeps its schedule.
+    pub fn add(
+        &self,
+        entry: &NewVocabularyEntry,
+        now: i64,
+    ) -> Result<VocabularyEntry, VocabularyError> {
+        let word = entry.word.trim();
+        if word.is_empty() {
+            return Err(VocabularyError::Invalid("word must not be empty"));
+        }
+        if word.contains('\n') || word.chars().count() > MAX_WORD_CHARS {
+            return Err(VocabularyError::Invalid(
+                "only single words and short phrases can be starred",
+            ));
+        }
+        if entry.translation.trim().is_empty() {
+            return Err(VocabularyError::Invalid("translation must not be empty"));
+        }
+
+        let conn = self.conn.lock().expect("vocabulary lock");
+        conn.execute(
+            "INSERT INTO vocabulary (word, translation, context, source_lang, target_lang, \
+             created_at, ease, interval_days, repetitions, lapses, due_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 0, 0, 0, ?6) \
+             ON CONFLICT (word, source_lang, target_lang) \
+             DO UPDATE SET translation = excluded.translation, context = excluded.context",
+            params![
+                word,
+                entry.translation.trim(),
+                entry.context.trim(),
+                entry.source_lang,
+                entry.target_lang,
+                now,
+                INITIAL_EASE,
+            ],
+        )?;
+        Ok(conn.query_row(
+            &format!(
+                "SELECT {COLUMNS} FROM vocabulary \
+                 WHERE word = ?1 AND source_lang = ?2 AND target_lang = ?3"
+            ),
+            params![word, entry.source_lang, entry.target_lang],
+            read_entry,
+        )?)
+    }
+
+    /// Every starred word, newest first.
+    pub fn entries(&self) -> Result<Vec<VocabularyEntry>, VocabularyError> {
+        self.query(
+            &format!("SELECT {COLUMNS} FROM vocabulary ORDER BY id DESC"),
+            params![],
+        )
+    }
+
+    /// Words whose review is due at `now`, most overdue first.
+    pub fn due(&self, now: i64, limit: u32) -> Result<Vec<VocabularyEntry>, VocabularyError> {
+        self.query(
+            &format!(
+                "SELECT {COLUMNS} FROM vocabulary WHERE due_at <= ?1 \
+                 ORDER BY due_at ASC LIMIT ?2"
+            ),
+            params![now, limit],
+        )
+    }
+
+    /// Records a review graded 0–5 and schedules the next one.
+    pub fn review(&self, id: i64, grade: u8, now: i64) -> Result<VocabularyEntry, VocabularyError> {
+        if grade > 5 {
+            return Err(VocabularyError::Invalid("grade must be between 0 and 5"));
+        }
+        let conn = self.conn.lock().expect("vocabulary lock");
+        let mut entry = conn
+            .query_row(
+                &format!("SELECT {COLUMNS} FROM vocabulary WHERE id = ?1"),
+                params![id],
+                read_entry,
+            )
+            .optional()?
+            .ok_or(VocabularyError::NotFound(id))?;
+        entry.review(grade, now);
+        conn.execute(
+            "UPDATE vocabulary SET ease = ?2, interval_days = ?3, repetitions = ?4, \
+             lapses = ?5, due_at = ?6 WHERE id = ?1",
+            params![
+                id,
+                entry.ease,
+                entry.interval_days,
+                entry.repetitions,
+                entry.lapses,
+                entry.due_at
+            ],
+        )?;
+        Ok(entry)
+    }
+
+    pub fn remove(&self, id: i64) -> Result<bool, VocabularyError> {
+        let conn = self.conn.lock().expect("vocabulary lock");
+        Ok(conn.execute("DELETE FROM vocabulary WHERE id = ?1", params![id])? > 0)
+    }
+
+    /// Tab-separated `word, translation, context, tags` rows, one per note, for
+    /// Anki's "Import File" with HTML disabled.
+    pub fn export_tsv(&self) -> Result<String, VocabularyError> {
+        let mut output = String::from("#separator:tab\n#html:false\n#tags column:4\n");
+        for entry in self.entries()?.into_iter().rev() {
+            let tags = anki::tags(&entry);
+            let fields = [
+                entry.word.as_str(),
+                entry.translation.as_str(),
+                entry.context.as_str(),
+                tags.as_str(),
+            ]
+            .map(tsv_field);
+            output.push_str(&fields.join("\t"));
+            output.push('\n');
+        }
+        Ok(output)
+    }
+
+    /// An `.apkg` deck with one "word → translation" card per entry.
+    pub fn export_apkg(&self, deck_name: &str, now: i64) -> Result<Vec<u8>, VocabularyError> {
+        let mut entries = self.entries()?;
+        entries.reverse();
+        anki::build_package(deck_name, &entries, now)
+    }
+
+    fn query(
+        &self,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<VocabularyEntry>, VocabularyError> {
+        let conn = self.conn.lock().expect("vocabulary lock");
+        let mut statement = conn.prepare(sql)?;
+        let entries = statement
+            .query_map(params, read_entry)?
+            .collect::<Result<_, _>>()?;
+        Ok(entries)
+    }
+}
+
+fn read_entry(row: &Row<'_>) -> rusqlite::Result<VocabularyEntry> {
+    Ok(VocabularyEntry {
+        id: row.get(0)?,
+        word: row.get(1)?,
+        translation: row.get(2)?,
+        context: row.get(3)?,
+        source_lang: row.get(4)?,
+        target_lang: row.get(5)?,
+        created_at: row.get(6)?,
+        ease: row.get(7)?,
+        interval_days: row.get(8)?,
+        repetitions: row.get(9)?,
+        lapses: row.get(10)?,
+        due_at: row.get(11)?,
+    })
+}
+
+/// Tabs and line breaks would split the row, so they become spaces.
+fn tsv_field(value: &str) -> String {
+    value
+        .split(['\t', '\r', '\n'])
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000_000;
+
+    fn new_entry() -> VocabularyEntry {
+        VocabularyEntry {
+            id: 1,
+            word: "serendipity".to_string(),
+            translation: "意外发现".to_string(),
+            context: String::new(),
+            source_lang: "English".to_string(),
+            target_lang: "中文".to_string(),
+            created_at: NOW,
+            ease: INITIAL_EASE,
+            interval_days: 0,
+            repetitions: 0,
+            lapses: 0,
+            due_at: NOW,
+        }
+    }
+
+    #[test]
+    fn successful_reviews_grow_the_interval() {
+        let mut entry = new_entry();
+        let intervals: Vec<u32> = (0..3)
+            .map(|_| {
+                entry.review(4, NOW);
+                entry.interval_days
+            })
+            .collect();
+        assert_eq!(intervals, [1, 6, 15]);
+        assert_eq!(entry.repetitions, 3);
+        assert_eq!(entry.ease, INITIAL_EASE);
+        assert_eq!(entry.due_at, NOW + 15 * DAY_MS);
+    }
+
+    #[test]
+    fn perfect_recall_raises_the_ease() {
+        let mut entry = new_entry();
+        entry.review(5, NOW);
+        assert!((entry.ease - (INITIAL_EASE + 0.1)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn grades_below_three_reset_the_schedule() {
+        let mut entry = new_entry();
+        for _ in 0..3 {
+            entry.review(4, NOW);
+        }
+        entry.review(2, NOW);
+        assert_eq!(entry.repetitions, 0);
+        assert_eq!(entry.interval_days, 1);
+        assert_eq!(entry.lapses, 1);
+        assert!((entry.ease - (INITIAL_EASE - 0.32)).abs() < 1e-9);
+        assert_eq!(entry.due_at, NOW + DAY_MS);
+
+        entry.review(4, NOW);
+        assert_eq!((entry.repetitions, entry.interval_days), (1, 1));
+    }
+
+    #[test]
+    fn ease_never_drops_below_the_minimum() {
+        let mut entry = new_entry();
+        for _ in 0..10 {
+            entry.review(0, NOW);
+        }
+        assert_eq!(entry.ease, MIN_EASE);
+        assert_eq!(entry.lapses, 10);
+    }
+}
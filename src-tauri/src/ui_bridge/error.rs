@@ -0,0 +1,112 @@
+use crate::api_client::ApiError;
+use crate::storage::{
+    CacheError, CredentialError, GlossaryError, HistoryError, SettingsError,
+    TranslationMemoryError, VocabularyError,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Error returned by every command, so the frontend can branch on `kind`
+/// instead of matching English text.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandError {
+    /// Stable identifier such as `unauthorized` or `invalid_settings`.
+    pub kind: &'static str,
+    pub message: String,
+    /// Whether repeating the same command may succeed.
+    pub retryable: bool,
+    pub details: Option<Value>,
+}
+
+impl CommandError {
+    fn new(kind: &'static str, message: impl ToString) -> Self {
+        Self {
+            kind,
+            message: message.to_string(),
+            retryable: false,
+            details: None,
+        }
+    }
+}
+
+impl From<ApiError> for CommandError {
+    fn from(err: ApiError) -> Self {
+        Self {
+            kind: err.code(),
+            retryable: err.is_retryable(),
+            details: match &err {
+                ApiError::BudgetExceeded(usage) => serde_json::to_value(usage).ok(),
+                _ => err
+                    .provider_error()
+                    .map(|error| serde_json::to_value(error).unwrap_or_default()),
+            },
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<CredentialError> for CommandError {
+    fn from(err: CredentialError) -> Self {
+        Self::new(err.code(), &err)
+    }
+}
+
+impl From<SettingsError> for CommandError {
+    fn from(err: SettingsError) -> Self {
+        match err {
+            SettingsError::Invalid(invalid) => Self {
+                details: Some(json!({ "errors": invalid.errors })),
+                ..Self::new("invalid_settings", &invalid)
+            },
+            SettingsError::Prompt(prompt) => Self {
+                details: Some(json!({ "field": prompt.field(), "code": prompt.code() })),
+                ..Self::new("invalid_prompt", &prompt)
+            },
+            SettingsError::Network(network) => Self::new("invalid_network", network),
+            other => Self::new("settings_storage", other),
+        }
+    }
+}
+
+impl From<CacheError> for CommandError {
+    fn from(err: CacheError) -> Self {
+        Self::new("cache_storage", err)
+    }
+}
+
+impl From<HistoryError> for CommandError {
+    fn from(err: HistoryError) -> Self {
+        Self::new("history_storage", err)
+    }
+}
+
+impl From<VocabularyError> for CommandError {
+    fn from(err: VocabularyError) -> Self {
+        match err {
+            VocabularyError::Invalid(_) => Self::new("invalid_vocabulary", err),
+            VocabularyError::NotFound(_) => Self::new("not_found", err),
+            other => Self::new("vocabulary_storage", other),
+        }
+    }
+}
+
+impl From<TranslationMemoryError> for CommandError {
+    fn from(err: TranslationMemoryError) -> Self {
+        match err {
+            TranslationMemoryError::Sqlite(_) => Self::new("translation_memory_storage", err),
+            other => Self::new("invalid_tmx", other),
+        }
+    }
+}
+
+impl From<GlossaryError> for CommandError {
+    fn from(err: GlossaryError) -> Self {
+        match err {
+            GlossaryError::Parse { line, .. } => Self {
+                details: Some(json!({ "line": line })),
+                ..Self::new("invalid_glossary", &err)
+            },
+            other => Self::new("glossary_storage", other),
+        }
+    }
+}
@@ -1,9 +1,20 @@
-use crate::api_client::TranslationRequest;
-use crate::core::AppState;
-use crate::storage::{AppSettings, CredentialStore};
-use tauri::{AppHandle, Manager, State};
+use crate::api_client::{
+    KeyCheck, KeyHealth, ModelInfo, TranslationDelta, TranslationObserver, TranslationRequest,
+    TranslationRetry,
+};
+use crate::core::{AppState, CacheStats, RequestOrigin};
+use crate::storage::{
+    ApiKeyStatus, AppSettings, BudgetWarning, CredentialBackendStatus, CredentialStore,
+    ExportFormat, GlossaryEntry, HistoryPage, NewVocabularyEntry, UsageStats, VocabularyEntry,
+    DEFAULT_KEY_NAME,
+};
+use tauri::{AppHandle, Manager, State, Window};
 use tracing::{info, warn};
 
+mod error;
+
+pub use error::CommandError;
+
 pub struct UiBridge;
 
 impl UiBridge {
@@ -20,6 +31,12 @@ impl UiBridge {
         let _ = app.emit_all("trigger-screenshot", ());
     }
 
+    pub fn emit_translation_delta(app: &AppHandle, delta: &TranslationDelta) {
+        if let Err(err) = app.emit_all("translation-delta", delta) {
+            warn!(?err, "failed to emit translation delta");
+        }
+    }
+
     pub fn open_settings(app: &AppHandle) {
         let _ = app.emit_all("open-settings", ());
         if let Some(window) = app.get_window("main") {
@@ -29,50 +46,408 @@ impl UiBridge {
     }
 }
 
+struct DeltaEmitter {
+    app: AppHandle,
+}
+
+impl TranslationObserver for DeltaEmitter {
+    fn on_delta(&self, delta: &TranslationDelta) {
+        UiBridge::emit_translation_delta(&self.app, delta);
+    }
+
+    fn on_retry(&self, retry: &TranslationRetry) {
+        if let Err(err) = self.app.emit_all("translation-retry", retry) {
+            warn!(?err, "failed to emit translation retry");
+        }
+    }
+
+    fn on_budget_warning(&self, warning: &BudgetWarning) {
+        if let Err(err) = self.app.emit_all("budget-warning", warning) {
+            warn!(?err, "failed to emit budget warning");
+        }
+    }
+}
+
+const HISTORY_PAGE_SIZE: u32 = 50;
+const REVIEW_BATCH_SIZE: u32 = 20;
+
 pub struct AppCommands;
 
 impl AppCommands {
+    /// Supersedes the unfinished translations started the same way: from a
+    /// selection, or by hand in the same window.
     #[tauri::command]
     pub async fn translate(
+        app: AppHandle,
+        window: Window,
+        state: State<'_, AppState>,
+        request: TranslationRequest,
+    ) -> Result<crate::api_client::TranslationResponse, CommandError> {
+        let origin = match request.selection_source {
+            Some(_) => RequestOrigin::Selection,
+            None => RequestOrigin::Window(window.label().to_string()),
+        };
+        let emitter = DeltaEmitter { app };
+        state
+            .core()
+            .translate_text(request, origin, Some(&emitter))
+            .await
+            .map_err(CommandError::from)
+    }
+
+    /// Translates a word to be starred, leaving the translation on screen running.
+    #[tauri::command]
+    pub async fn lookup_word(
         state: State<'_, AppState>,
         request: TranslationRequest,
-    ) -> Result<crate::api_client::TranslationResponse, String> {
+    ) -> Result<crate::api_client::TranslationResponse, CommandError> {
         state
             .core()
-            .translate_text(request)
+            .lookup_word(request)
             .await
-            .map_err(|err| err.to_string())
+            .map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn cancel_translation(
+        state: State<'_, AppState>,
+        request_id: String,
+    ) -> Result<bool, CommandError> {
+        Ok(state.core().cancel_translation(&request_id))
+    }
+
+    #[tauri::command]
+    pub fn save_settings(
+        state: State<'_, AppState>,
+        settings: AppSettings,
+    ) -> Result<(), CommandError> {
+        state
+            .core()
+            .update_settings(settings)
+            .map_err(CommandError::from)
     }
 
     #[tauri::command]
-    pub fn save_settings(state: State<'_, AppState>, settings: AppSettings) -> Result<(), String> {
-        state.core().update_settings(settings);
-        Ok(())
+    pub fn select_prompt_profile(
+        state: State<'_, AppState>,
+        name: String,
+    ) -> Result<(), CommandError> {
+        state
+            .core()
+            .select_prompt_profile(&name)
+            .map_err(CommandError::from)
     }
 
     #[tauri::command]
-    pub fn load_settings(state: State<'_, AppState>) -> Result<AppSettings, String> {
+    pub fn load_settings(state: State<'_, AppState>) -> Result<AppSettings, CommandError> {
         Ok(state.core().load_settings())
     }
 
     #[tauri::command]
-    pub fn set_api_key(state: State<'_, AppState>, api_key: String) -> Result<(), String> {
+    pub fn list_providers(state: State<'_, AppState>) -> Result<Vec<String>, CommandError> {
+        Ok(state.core().provider_names())
+    }
+
+    #[tauri::command]
+    pub fn import_glossary(
+        state: State<'_, AppState>,
+        content: String,
+        source_lang: String,
+        target_lang: String,
+    ) -> Result<usize, CommandError> {
+        state
+            .core()
+            .glossary()
+            .import(&content, &source_lang, &target_lang)
+            .map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn list_glossary(state: State<'_, AppState>) -> Result<Vec<GlossaryEntry>, CommandError> {
+        Ok(state.core().glossary().entries())
+    }
+
+    #[tauri::command]
+    pub fn remove_glossary_entry(
+        state: State<'_, AppState>,
+        source_lang: String,
+        target_lang: String,
+        term: String,
+    ) -> Result<bool, CommandError> {
+        state
+            .core()
+            .glossary()
+            .remove(&source_lang, &target_lang, &term)
+            .map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn set_api_key(
+        state: State<'_, AppState>,
+        api_key: String,
+        name: Option<String>,
+    ) -> Result<(), CommandError> {
         state
             .core()
             .credential_store()
-            .write_api_key(&api_key)
-            .map_err(|err| err.to_string())
+            .set_api_key(name.as_deref().unwrap_or(DEFAULT_KEY_NAME), &api_key)
+            .map_err(CommandError::from)
     }
 
+    /// Unlocks the encrypted-file credential backend; a no-op for OS keyrings.
     #[tauri::command]
-    pub fn read_api_key(state: State<'_, AppState>) -> Result<Option<String>, String> {
-        match state.core().credential_store().read_api_key() {
-            Ok(value) => Ok(value),
-            Err(err) => {
-                warn!(?err, "failed to read api key");
-                Ok(None)
-            }
-        }
+    pub fn unlock_credentials(
+        state: State<'_, AppState>,
+        passphrase: String,
+    ) -> Result<(), CommandError> {
+        state
+            .core()
+            .credential_store()
+            .unlock(&passphrase)
+            .map_err(CommandError::from)
+    }
+
+    /// Tells the UI where the keys are kept, including when the keyring was
+    /// unavailable and they went into the encrypted file.
+    #[tauri::command]
+    pub fn credential_backend(
+        state: State<'_, AppState>,
+    ) -> Result<CredentialBackendStatus, CommandError> {
+        Ok(state.core().credential_store().backend_status())
+    }
+
+    /// Reports whether a key is stored without sending the key itself to the webview.
+    #[tauri::command]
+    pub fn api_key_status(state: State<'_, AppState>) -> Result<ApiKeyStatus, CommandError> {
+        state
+            .core()
+            .credential_store()
+            .api_key_status()
+            .map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub async fn test_api_key(
+        state: State<'_, AppState>,
+        name: Option<String>,
+    ) -> Result<KeyCheck, CommandError> {
+        state
+            .core()
+            .test_api_key(name.as_deref())
+            .await
+            .map_err(CommandError::from)
+    }
+
+    /// Deletes the named key, or all keys when no name is given.
+    #[tauri::command]
+    pub fn delete_api_key(
+        state: State<'_, AppState>,
+        name: Option<String>,
+    ) -> Result<(), CommandError> {
+        state
+            .core()
+            .credential_store()
+            .delete_api_key(name.as_deref())
+            .map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn api_key_health(state: State<'_, AppState>) -> Result<Vec<KeyHealth>, CommandError> {
+        state.core().key_health().map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn usage_stats(state: State<'_, AppState>) -> Result<UsageStats, CommandError> {
+        Ok(state.core().usage_stats())
+    }
+
+    #[tauri::command]
+    pub fn list_history(
+        state: State<'_, AppState>,
+        offset: Option<u32>,
+        limit: Option<u32>,
+    ) -> Result<HistoryPage, CommandError> {
+        state
+            .core()
+            .list_history(offset.unwrap_or(0), limit.unwrap_or(HISTORY_PAGE_SIZE))
+            .map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn search_history(
+        state: State<'_, AppState>,
+        query: String,
+        offset: Option<u32>,
+        limit: Option<u32>,
+    ) -> Result<HistoryPage, CommandError> {
+        state
+            .core()
+            .search_history(
+                &query,
+                offset.unwrap_or(0),
+                limit.unwrap_or(HISTORY_PAGE_SIZE),
+            )
+            .map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn delete_history(
+        state: State<'_, AppState>,
+        ids: Vec<i64>,
+    ) -> Result<usize, CommandError> {
+        state
+            .core()
+            .delete_history(&ids)
+            .map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn clear_history(state: State<'_, AppState>) -> Result<usize, CommandError> {
+        state.core().clear_history().map_err(CommandError::from)
+    }
+
+    /// Returns the exported text; the frontend decides where to save it.
+    #[tauri::command]
+    pub fn export_history(
+        state: State<'_, AppState>,
+        format: ExportFormat,
+    ) -> Result<String, CommandError> {
+        state
+            .core()
+            .export_history(format)
+            .map_err(CommandError::from)
+    }
+
+    /// Stars a word or phrase from a finished translation.
+    #[tauri::command]
+    pub fn star_word(
+        state: State<'_, AppState>,
+        entry: NewVocabularyEntry,
+    ) -> Result<VocabularyEntry, CommandError> {
+        state.core().star_word(&entry).map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn list_vocabulary(
+        state: State<'_, AppState>,
+    ) -> Result<Vec<VocabularyEntry>, CommandError> {
+        state.core().list_vocabulary().map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn due_vocabulary(
+        state: State<'_, AppState>,
+        limit: Option<u32>,
+    ) -> Result<Vec<VocabularyEntry>, CommandError> {
+        state
+            .core()
+            .due_vocabulary(limit.unwrap_or(REVIEW_BATCH_SIZE))
+            .map_err(CommandError::from)
+    }
+
+    /// `grade` runs from 0 (forgot) to 5 (perfect recall).
+    #[tauri::command]
+    pub fn review_vocabulary(
+        state: State<'_, AppState>,
+        id: i64,
+        grade: u8,
+    ) -> Result<VocabularyEntry, CommandError> {
+        state
+            .core()
+            .review_vocabulary(id, grade)
+            .map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn remove_vocabulary(state: State<'_, AppState>, id: i64) -> Result<(), CommandError> {
+        state
+            .core()
+            .remove_vocabulary(id)
+            .map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn export_vocabulary_tsv(state: State<'_, AppState>) -> Result<String, CommandError> {
+        state
+            .core()
+            .export_vocabulary_tsv()
+            .map_err(CommandError::from)
+    }
+
+    /// Returns the `.apkg` file contents; the frontend decides where to save it.
+    #[tauri::command]
+    pub fn export_vocabulary_apkg(
+        state: State<'_, AppState>,
+        deck_name: Option<String>,
+    ) -> Result<Vec<u8>, CommandError> {
+        state
+            .core()
+            .export_vocabulary_apkg(deck_name.as_deref())
+            .map_err(CommandError::from)
+    }
+
+    /// Takes the file contents; the frontend reads the file the user picked.
+    #[tauri::command]
+    pub fn import_tmx(state: State<'_, AppState>, content: String) -> Result<usize, CommandError> {
+        state
+            .core()
+            .import_tmx(&content)
+            .map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn export_tmx(state: State<'_, AppState>) -> Result<String, CommandError> {
+        state.core().export_tmx().map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn translation_memory_size(state: State<'_, AppState>) -> Result<u64, CommandError> {
+        state
+            .core()
+            .translation_memory_size()
+            .map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn clear_translation_memory(state: State<'_, AppState>) -> Result<usize, CommandError> {
+        state
+            .core()
+            .clear_translation_memory()
+            .map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn cache_stats(state: State<'_, AppState>) -> Result<CacheStats, CommandError> {
+        state.core().cache_stats().map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn clear_cache(state: State<'_, AppState>) -> Result<usize, CommandError> {
+        state.core().clear_cache().map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub fn set_cache_limits(
+        state: State<'_, AppState>,
+        memory_entries: usize,
+        max_disk_bytes: u64,
+    ) -> Result<(), CommandError> {
+        state
+            .core()
+            .set_cache_limits(memory_entries, max_disk_bytes)
+            .map_err(CommandError::from)
+    }
+
+    #[tauri::command]
+    pub async fn list_models(
+        state: State<'_, AppState>,
+        refresh: Option<bool>,
+    ) -> Result<Vec<ModelInfo>, CommandError> {
+        state
+            .core()
+            .list_models(refresh.unwrap_or(false))
+            .await
+            .map_err(CommandError::from)
     }
 }
 
@@ -6,35 +6,185 @@ type TranslationRequest = {
   source_lang: string;
   target_lang: string;
   model: string;
+  stream: boolean;
+  request_id?: string | null;
+  selection_source?: SelectionSource | null;
 };
 
+type SelectionSource = "UiAutomation" | "ClipboardFallback" | "OcrPlaceholder";
+
 type TranslationResponse = {
+  request_id?: string | null;
   translated_text: string;
   detected_source_lang?: string | null;
+  direction: { source_lang: string; target_lang: string; swapped: boolean };
+  glossary_violations: { term: string; expected: string }[];
+  usage?: { prompt_tokens: number; completion_tokens: number; total_tokens: number } | null;
+  cached: boolean;
+};
+
+type TranslationDelta = {
+  request_id?: string | null;
+  delta: string;
+};
+
+type TranslationRetry = {
+  request_id?: string | null;
+  attempt: number;
+  max_attempts: number;
+  delay_ms: number;
+  reason: string;
+};
+
+type BudgetWarning = {
+  request_id?: string | null;
+  usage: { period: "daily" | "monthly"; metric: "tokens" | "cost"; used: number; limit: number };
+  threshold: number;
+  fallback_model?: string | null;
+};
+
+type CommandError = {
+  kind: string;
+  message: string;
+  retryable: boolean;
+  details?: { code?: string | null; type?: string | null; message?: string } | null;
+};
+
+const ERROR_MESSAGES: Record<string, string> = {
+  missing_api_key: "尚未设置 API Key，请在设置中填写",
+  unauthorized: "API Key 无效或已过期，请在设置中更新",
+  rate_limited: "请求过于频繁，请稍后再试",
+  model_unavailable: "模型暂时不可用，请稍后再试",
+  insufficient_balance: "账户余额不足，请充值后再试",
+  context_length_exceeded: "文本过长，超出模型上下文长度，请缩短后重试",
+  content_filtered: "内容被服务商安全策略拦截",
+  invalid_model: "模型不存在，请在设置中检查模型名称",
+  bad_request: "请求参数有误",
+  server_error: "服务商暂时出错，请稍后再试",
+  reserved_request_id: "请求 ID 使用了保留前缀",
+  network: "网络连接失败，请检查网络或代理设置",
+  invalid_network: "网络设置无法生效，请检查代理、请求头和 CA 证书文件",
+  credentials_locked: "凭据已加密锁定，请先输入口令解锁",
+  credentials_unavailable: "没有可用的凭据存储",
+  budget_exceeded: "已达到用量预算上限，请调整预算或等待下个周期",
+  cache_storage: "翻译缓存读写失败",
+  invalid_vocabulary: "只能收藏单词或短语",
+  vocabulary_storage: "生词本读写失败",
+  invalid_tmx: "TMX 文件格式无效",
+  translation_memory_storage: "翻译记忆读写失败",
+};
+
+function describeError(error: unknown): string {
+  const payload = error as CommandError;
+  const message = payload?.kind ? ERROR_MESSAGES[payload.kind] : undefined;
+  if (!message) {
+    return payload?.message ?? String(error);
+  }
+  const providerCode = payload.details?.code;
+  return providerCode ? `${message}（${providerCode}）` : message;
+}
+
+type PromptProfile = {
+  name: string;
+  system_template: string;
+  user_template: string;
+};
+
+type AppSettings = {
+  prompt_profiles: PromptProfile[];
+  active_prompt_profile: string;
+};
+
+type ModelInfo = {
+  id: string;
+  owned_by?: string | null;
 };
 
 type SelectionEvent = {
   text: string;
-  source: "UiAutomation" | "ClipboardFallback" | "OcrPlaceholder";
+  source: SelectionSource;
   bounds?: { left: number; top: number; right: number; bottom: number } | null;
 };
 
+type CredentialBackendStatus = {
+  backend?: string | null;
+  file_fallback: boolean;
+  locked: boolean;
+  rejected: string[];
+};
+
 const sourceText = document.querySelector<HTMLTextAreaElement>("#sourceText")!;
 const targetText = document.querySelector<HTMLTextAreaElement>("#targetText")!;
 const status = document.querySelector<HTMLParagraphElement>("#status")!;
 const sourceLang = document.querySelector<HTMLSelectElement>("#sourceLang")!;
 const targetLang = document.querySelector<HTMLSelectElement>("#targetLang")!;
 const modelInput = document.querySelector<HTMLInputElement>("#model")!;
+const promptProfile = document.querySelector<HTMLSelectElement>("#promptProfile")!;
+const modelOptions = document.querySelector<HTMLDataListElement>("#modelOptions")!;
 
 modelInput.value = "Qwen/Qwen2.5-7B-Instruct";
 
-async function translate() {
-  status.textContent = "翻译中...";
+async function loadPromptProfiles() {
+  const settings = await invoke<AppSettings>("load_settings");
+  promptProfile.replaceChildren(
+    ...settings.prompt_profiles.map((profile) => new Option(profile.name, profile.name)),
+  );
+  promptProfile.value = settings.active_prompt_profile;
+}
+
+promptProfile.addEventListener("change", async () => {
+  try {
+    await invoke("select_prompt_profile", { name: promptProfile.value });
+    status.textContent = `已切换模板：${promptProfile.value}`;
+  } catch (error) {
+    status.textContent = `切换模板失败: ${describeError(error)}`;
+  }
+});
+
+loadPromptProfiles();
+
+async function loadModels() {
+  try {
+    const models = await invoke<ModelInfo[]>("list_models");
+    modelOptions.replaceChildren(...models.map((model) => new Option(model.id, model.id)));
+  } catch {
+    // Without a key or network the model stays free-form.
+  }
+}
+
+loadModels();
+
+async function checkCredentialBackend() {
+  try {
+    const backend = await invoke<CredentialBackendStatus>("credential_backend");
+    if (!backend.backend) {
+      status.textContent = ERROR_MESSAGES.credentials_unavailable;
+    } else if (backend.file_fallback) {
+      status.textContent = backend.locked
+        ? "系统钥匙串不可用，API Key 保存在加密文件中，请先输入口令解锁"
+        : "系统钥匙串不可用，API Key 保存在加密文件中";
+    }
+  } catch {
+    // The status line keeps its default text.
+  }
+}
+
+checkCredentialBackend();
+
+let requestCounter = 0;
+let currentRequestId: string | null = null;
+let lastTranslation: { text: string; response: TranslationResponse } | null = null;
+
+async function translate(selectionSource: SelectionSource | null = null) {
+  const requestId = `ui-${Date.now()}-${requestCounter++}`;
   const payload: TranslationRequest = {
     text: sourceText.value.trim(),
     source_lang: sourceLang.value,
     target_lang: targetLang.value,
     model: modelInput.value.trim(),
+    stream: true,
+    request_id: requestId,
+    selection_source: selectionSource,
   };
 
   if (!payload.text) {
@@ -42,12 +192,40 @@ async function translate() {
     return;
   }
 
+  if (currentRequestId && !selectionSource) {
+    invoke("cancel_translation", { requestId: currentRequestId });
+  }
+  currentRequestId = requestId;
+  status.textContent = "翻译中...";
+  targetText.value = "";
   try {
     const response = await invoke<TranslationResponse>("translate", { request: payload });
+    if (response.request_id !== currentRequestId) {
+      return;
+    }
     targetText.value = response.translated_text;
-    status.textContent = "翻译完成";
+    lastTranslation = { text: payload.text, response };
+    const violations = response.glossary_violations
+      .map((violation) => `${violation.term}→${violation.expected}`)
+      .join("，");
+    const detected = response.direction.swapped
+      ? `（原文已是目标语言，改为 ${response.direction.source_lang} → ${response.direction.target_lang}）`
+      : response.detected_source_lang
+        ? `（检测到 ${response.detected_source_lang}）`
+        : "";
+    const tokens = response.cached
+      ? "，来自缓存"
+      : response.usage
+        ? `，${response.usage.total_tokens} tokens`
+        : "";
+    status.textContent = violations
+      ? `翻译完成${detected}${tokens}（术语未按要求翻译：${violations}）`
+      : `翻译完成${detected}${tokens}`;
   } catch (error) {
-    status.textContent = `翻译失败: ${String(error)}`;
+    if (requestId !== currentRequestId) {
+      return;
+    }
+    status.textContent = `翻译失败: ${describeError(error)}`;
   }
 }
 
@@ -67,6 +245,48 @@ document.querySelector<HTMLButtonElement>("#copySource")!.addEventListener("clic
   status.textContent = "原文已复制";
 });
 
+// Stars the selected part of the source, translated on its own with the source as
+// context sentence, or the whole source if nothing is selected.
+document.querySelector<HTMLButtonElement>("#starWord")!.addEventListener("click", async () => {
+  if (!lastTranslation) {
+    status.textContent = "请先完成一次翻译";
+    return;
+  }
+  const { text, response } = lastTranslation;
+  const selected = sourceText.value
+    .slice(sourceText.selectionStart, sourceText.selectionEnd)
+    .trim();
+  const word = selected || text;
+  try {
+    const translation = selected
+      ? (
+          await invoke<TranslationResponse>("lookup_word", {
+            request: {
+              text: selected,
+              context: text,
+              source_lang: response.direction.source_lang,
+              target_lang: response.direction.target_lang,
+              model: modelInput.value.trim(),
+              stream: false,
+            },
+          })
+        ).translated_text
+      : response.translated_text;
+    await invoke("star_word", {
+      entry: {
+        word,
+        translation,
+        context: selected ? text : "",
+        source_lang: response.direction.source_lang,
+        target_lang: response.direction.target_lang,
+      },
+    });
+    status.textContent = `已收藏「${word}」`;
+  } catch (error) {
+    status.textContent = `收藏失败: ${describeError(error)}`;
+  }
+});
+
 document.querySelector<HTMLButtonElement>("#speakText")!.addEventListener("click", () => {
   if (!targetText.value) {
     status.textContent = "无译文可朗读";
@@ -77,10 +297,37 @@ document.querySelector<HTMLButtonElement>("#speakText")!.addEventListener("click
   status.textContent = "朗读中";
 });
 
+listen<TranslationDelta>("translation-delta", (event) => {
+  if (event.payload.request_id !== currentRequestId) {
+    return;
+  }
+  targetText.value += event.payload.delta;
+});
+
+listen<TranslationRetry>("translation-retry", (event) => {
+  if (event.payload.request_id !== currentRequestId) {
+    return;
+  }
+  status.textContent = `重试中 (${event.payload.attempt}/${event.payload.max_attempts})...`;
+});
+
+listen<BudgetWarning>("budget-warning", (event) => {
+  if (event.payload.request_id !== currentRequestId) {
+    return;
+  }
+  const { usage, fallback_model } = event.payload;
+  const period = usage.period === "daily" ? "今日" : "本月";
+  const percent = Math.round((usage.used / usage.limit) * 100);
+  status.textContent = fallback_model
+    ? `${period}预算已用尽，改用 ${fallback_model} 翻译中...`
+    : `${period}预算已使用 ${percent}%`;
+});
+
 listen<SelectionEvent>("selection-event", (event) => {
   if (event.payload.text) {
     sourceText.value = event.payload.text;
     status.textContent = `捕获到选中文本（${event.payload.source}）`;
+    translate(event.payload.source);
   }
 });
 